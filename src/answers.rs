@@ -0,0 +1,402 @@
+use custom_error::custom_error;
+use inquire::error::InquireResult;
+use inquire::InquireError;
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use std::cell::RefCell;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+custom_error! {pub AnswersError
+    Read{path:String, source:std::io::Error} = "Could not read answers file {path}: {source}",
+    Toml{source:toml::de::Error} = "Invalid answers file: {source}",
+    Json{source:serde_json::Error} = "Invalid answers file: {source}",
+    AlreadyLoaded = "An answers file was already loaded",
+    AlreadyRecording = "Answers are already being recorded",
+}
+
+custom_error! {pub InvalidAnswer
+    Invalid{question:String, answer:String, reason:String}
+        = "Invalid answer {answer} to \"{question}\": {reason}",
+}
+
+#[derive(Clone)]
+struct Recorder {
+    path: PathBuf,
+    secrets: bool,
+}
+
+thread_local! {
+    static ANSWERS: RefCell<Option<Map<String, Value>>> = const { RefCell::new(None) };
+    static RECORDER: RefCell<Option<Recorder>> = const { RefCell::new(None) };
+    static KEY: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Load the answers file, mapping question keys (eg. form field
+/// names) or question texts to the answer to give instead of asking.
+/// It's parsed as JSON if the file name ends in `.json`, otherwise as
+/// TOML. A file ending in `.jsonl` is read as a recording made with
+/// [`record_to`].
+pub fn load(path: &Path) -> Result<(), AnswersError> {
+    if ANSWERS.with(|a| a.borrow().is_some()) {
+        return Err(AnswersError::AlreadyLoaded);
+    }
+    let answers = read(path)?;
+    ANSWERS.with(|a| *a.borrow_mut() = Some(answers));
+    Ok(())
+}
+
+/// Run `f` with the answers in the file at `path`, if any, in place of
+/// those [`load`]ed.
+pub fn with_answers<T>(path: Option<&Path>, f: impl FnOnce() -> T) -> Result<T, AnswersError> {
+    let Some(path) = path else {
+        return Ok(f());
+    };
+    let answers = read(path)?;
+    let outer = ANSWERS.with(|a| a.replace(Some(answers)));
+    let result = f();
+    ANSWERS.with(|a| *a.borrow_mut() = outer);
+    Ok(result)
+}
+
+/// The answers in the file at `path`, see [`load`].
+fn read(path: &Path) -> Result<Map<String, Value>, AnswersError> {
+    let text = std::fs::read_to_string(path).map_err(|source| AnswersError::Read {
+        path: path.display().to_string(),
+        source,
+    })?;
+    Ok(match path.extension().and_then(|e| e.to_str()) {
+        Some("json") => serde_json::from_str(&text)?,
+        Some("jsonl") => {
+            let mut answers = Map::new();
+            for line in text.lines().filter(|l| !l.trim().is_empty()) {
+                let record: Value = serde_json::from_str(line)?;
+                // Redacted secrets still have to be typed in:
+                if record["redacted"] == true {
+                    continue;
+                }
+                let key = record["key"].as_str().or(record["question"].as_str());
+                if let Some(key) = key {
+                    answers.insert(key.to_string(), record["answer"].clone());
+                }
+            }
+            answers
+        }
+        _ => toml::from_str(&text)?,
+    })
+}
+
+/// Run `f` with `key` as the key to look up answers by, before falling
+/// back to the question text.
+pub fn with_key<T>(key: &str, f: impl FnOnce() -> T) -> T {
+    let outer = KEY.with(|k| k.replace(Some(key.to_string())));
+    let result = f();
+    KEY.with(|k| *k.borrow_mut() = outer);
+    result
+}
+
+/// The environment variable `SCRIPT_WIZARD_<PREFIX>_<KEY>` for the
+/// current key, if one is set.
+fn key_var(prefix: &str) -> Option<String> {
+    let key = KEY.with(|k| k.borrow().clone())?;
+    std::env::var(var_name(prefix, &key)).ok()
+}
+
+/// `SCRIPT_WIZARD_<PREFIX>_<KEY>`, with the key upper-cased and
+/// anything that can't be in a variable name made `_`.
+fn var_name(prefix: &str, key: &str) -> String {
+    let key: String = key
+        .chars()
+        .map(|c| match c.is_ascii_alphanumeric() {
+            true => c.to_ascii_uppercase(),
+            false => '_',
+        })
+        .collect();
+    format!("SCRIPT_WIZARD_{}_{}", prefix, key)
+}
+
+/// The canned answer for `question`, if there is one: from
+/// `SCRIPT_WIZARD_ANSWER_<KEY>`, or else the answers file.
+pub(crate) fn lookup(question: &str) -> Option<Value> {
+    if let Some(answer) = key_var("ANSWER") {
+        return Some(Value::String(answer));
+    }
+    let key = KEY.with(|k| k.borrow().clone());
+    ANSWERS.with(|a| {
+        let answers = a.borrow();
+        let answers = answers.as_ref()?;
+        key.and_then(|key| answers.get(&key))
+            .or_else(|| answers.get(question))
+            .cloned()
+    })
+}
+
+/// `SCRIPT_WIZARD_DEFAULT_<KEY>` if it is set, otherwise `default`.
+pub(crate) fn default_or(default: &str) -> String {
+    key_var("DEFAULT").unwrap_or(default.to_string())
+}
+
+/// The error for a canned answer that doesn't fit its question.
+pub(crate) fn invalid(question: &str, answer: &Value, reason: &str) -> InquireError {
+    InquireError::Custom(Box::new(InvalidAnswer::Invalid {
+        question: question.to_string(),
+        answer: answer.to_string(),
+        reason: reason.to_string(),
+    }))
+}
+
+/// A canned answer that must be a string.
+pub(crate) fn text(question: &str, answer: &Value) -> Result<String, InquireError> {
+    match answer {
+        Value::String(s) => Ok(s.clone()),
+        Value::Number(n) => Ok(n.to_string()),
+        _ => Err(invalid(question, answer, "expected a string")),
+    }
+}
+
+/// Append every answer given from now on to `path` as a line of JSON,
+/// with the prompt's key, kind, question and options. Answers to
+/// secret prompts are redacted unless `secrets` is set.
+pub fn record_to(path: &Path, secrets: bool) -> Result<(), AnswersError> {
+    if RECORDER.with(|r| r.borrow().is_some()) {
+        return Err(AnswersError::AlreadyRecording);
+    }
+    let recorder = Recorder {
+        path: path.to_path_buf(),
+        secrets,
+    };
+    RECORDER.with(|r| *r.borrow_mut() = Some(recorder));
+    Ok(())
+}
+
+/// Run `f` recording its answers to `path`, if given, in place of the
+/// file from [`record_to`].
+pub fn with_recording<T>(path: Option<&Path>, secrets: bool, f: impl FnOnce() -> T) -> T {
+    let Some(path) = path else {
+        return f();
+    };
+    let recorder = Recorder {
+        path: path.to_path_buf(),
+        secrets,
+    };
+    let outer = RECORDER.with(|r| r.replace(Some(recorder)));
+    let result = f();
+    RECORDER.with(|r| *r.borrow_mut() = outer);
+    result
+}
+
+fn record(kind: &str, question: &str, options: Vec<String>, answer: Value, secret: bool) {
+    let Some(recorder) = RECORDER.with(|r| r.borrow().clone()) else {
+        return;
+    };
+    let line = record_line(kind, question, options, answer, secret && !recorder.secrets);
+    let written = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&recorder.path)
+        .and_then(|mut f| writeln!(f, "{}", line));
+    if let Err(e) = written {
+        eprintln!(
+            "Could not record answer to {}: {}",
+            recorder.path.display(),
+            e
+        );
+    }
+}
+
+/// The line [`record`] writes, leaving out the answer if `redacted`.
+fn record_line(
+    kind: &str,
+    question: &str,
+    options: Vec<String>,
+    answer: Value,
+    redacted: bool,
+) -> Value {
+    let mut line = json!({
+        "key": KEY.with(|k| k.borrow().clone()),
+        "kind": kind,
+        "question": question,
+        "answer": match redacted {
+            true => Value::Null,
+            false => answer,
+        },
+    });
+    if !options.is_empty() {
+        line["options"] = json!(options);
+    }
+    if redacted {
+        line["redacted"] = json!(true);
+    }
+    line
+}
+
+/// Ask with `f`, recording the answer it gives.
+pub(crate) fn recorded<T: Serialize>(
+    kind: &str,
+    question: &str,
+    options: Vec<String>,
+    f: impl FnOnce() -> InquireResult<T>,
+) -> InquireResult<T> {
+    let answer = f()?;
+    record(kind, question, options, json!(answer), false);
+    Ok(answer)
+}
+
+/// Like [`recorded`], for answers that must not be stored in the clear.
+pub(crate) fn recorded_secret<T: Serialize>(
+    kind: &str,
+    question: &str,
+    f: impl FnOnce() -> InquireResult<T>,
+) -> InquireResult<T> {
+    let answer = f()?;
+    record(kind, question, vec![], json!(answer), true);
+    Ok(answer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ask::{ChooseBuilder, ConfirmBuilder, NumberBuilder, WizardError};
+
+    /// Read answers from a temporary file named `name`.
+    fn read_file(name: &str, text: &str) -> Result<Map<String, Value>, AnswersError> {
+        let path =
+            std::env::temp_dir().join(format!("script-wizard-{}-{}", std::process::id(), name));
+        std::fs::write(&path, text).unwrap();
+        let answers = read(&path);
+        std::fs::remove_file(path).unwrap();
+        answers
+    }
+
+    #[test]
+    fn reads_json_toml_and_recordings() {
+        let expected = json!({"What is your name?": "Tux", "port": 8080, "tags": ["a", "b"]});
+        let answers = read_file(
+            "answers.json",
+            r#"{"What is your name?": "Tux", "port": 8080, "tags": ["a", "b"]}"#,
+        );
+        assert_eq!(Value::Object(answers.unwrap()), expected);
+        let answers = read_file(
+            "answers.toml",
+            "\"What is your name?\" = \"Tux\"\nport = 8080\ntags = [\"a\", \"b\"]\n",
+        );
+        assert_eq!(Value::Object(answers.unwrap()), expected);
+        // A recording is keyed by the prompt's key, or else its question:
+        let answers = read_file(
+            "answers.jsonl",
+            concat!(
+                r#"{"key":null,"kind":"ask","question":"What is your name?","answer":"Tux"}"#,
+                "\n\n",
+                r#"{"key":"port","kind":"number","question":"Port?","answer":8080}"#,
+                "\n",
+                r#"{"key":"tags","kind":"select","question":"Tags?","answer":["a","b"],"#,
+                r#""options":["a","b","c"]}"#,
+            ),
+        );
+        assert_eq!(Value::Object(answers.unwrap()), expected);
+
+        assert!(matches!(
+            read_file("bad.json", "[1]"),
+            Err(AnswersError::Json { .. })
+        ));
+        assert!(matches!(
+            read_file("bad.toml", "x ="),
+            Err(AnswersError::Toml { .. })
+        ));
+        assert!(matches!(
+            read_file("bad.jsonl", "{\n"),
+            Err(AnswersError::Json { .. })
+        ));
+        let missing = read(Path::new("/nonexistent/answers.json"));
+        assert!(matches!(missing, Err(AnswersError::Read { .. })));
+    }
+
+    #[test]
+    fn names_variables_after_keys() {
+        assert_eq!(var_name("ANSWER", "name"), "SCRIPT_WIZARD_ANSWER_NAME");
+        assert_eq!(
+            var_name("ANSWER", "Db-Host"),
+            "SCRIPT_WIZARD_ANSWER_DB_HOST"
+        );
+        assert_eq!(
+            var_name("DEFAULT", "server.port2"),
+            "SCRIPT_WIZARD_DEFAULT_SERVER_PORT2"
+        );
+
+        std::env::set_var("SCRIPT_WIZARD_ANSWER_NAMES_VARIABLES_1", "yes");
+        assert_eq!(
+            with_key("names-variables.1", || key_var("ANSWER")).as_deref(),
+            Some("yes")
+        );
+        assert_eq!(with_key("names-variables.2", || key_var("ANSWER")), None);
+        assert_eq!(key_var("ANSWER"), None);
+        std::env::remove_var("SCRIPT_WIZARD_ANSWER_NAMES_VARIABLES_1");
+    }
+
+    #[test]
+    fn redacts_secrets_and_skips_them_when_read() {
+        let secret = with_key("password", || {
+            record_line("password", "Password?", vec![], json!("hunter2"), true)
+        });
+        assert_eq!(
+            secret.to_string(),
+            concat!(
+                r#"{"key":"password","kind":"password","question":"Password?","#,
+                r#""answer":null,"redacted":true}"#
+            )
+        );
+        let name = record_line("ask", "Name?", vec![], json!("Tux"), false);
+        assert_eq!(name.get("redacted"), None);
+
+        let answers = read_file("redacted.jsonl", &format!("{}\n{}\n", secret, name)).unwrap();
+        assert_eq!(Value::Object(answers), json!({"Name?": "Tux"}));
+    }
+
+    #[test]
+    fn records_to_one_file_at_a_time() {
+        let path = std::env::temp_dir().join("script-wizard-recording.jsonl");
+        assert!(record_to(&path, false).is_ok());
+        assert!(matches!(
+            record_to(&path, true),
+            Err(AnswersError::AlreadyRecording)
+        ));
+        RECORDER.with(|r| *r.borrow_mut() = None);
+    }
+
+    #[test]
+    fn rejects_answers_that_dont_fit() {
+        let invalid = |key: &str, answer: &str, f: &dyn Fn() -> Result<(), WizardError>| {
+            let var = var_name("ANSWER", key);
+            std::env::set_var(&var, answer);
+            let result = with_key(key, f);
+            std::env::remove_var(&var);
+            assert!(
+                matches!(result, Err(WizardError::InvalidAnswer { .. })),
+                "{}",
+                answer
+            );
+        };
+        invalid("rejects_answers.choose", "d", &|| {
+            ChooseBuilder::new("Pick")
+                .options(["a", "b"])
+                .prompt()
+                .map(|_| ())
+        });
+        invalid("rejects_answers.confirm", "maybe", &|| {
+            ConfirmBuilder::new("OK?").prompt().map(|_| ())
+        });
+        invalid("rejects_answers.number", "12", &|| {
+            NumberBuilder::new("Port?")
+                .max(Some(10.0))
+                .prompt()
+                .map(|_| ())
+        });
+
+        std::env::set_var("SCRIPT_WIZARD_ANSWER_REJECTS_ANSWERS_VALID", "b");
+        let chosen = with_key("rejects_answers.valid", || {
+            ChooseBuilder::new("Pick").options(["a", "b"]).prompt()
+        });
+        std::env::remove_var("SCRIPT_WIZARD_ANSWER_REJECTS_ANSWERS_VALID");
+        assert_eq!(chosen.unwrap(), "b");
+    }
+}
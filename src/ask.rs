@@ -1,11 +1,27 @@
-use std::process::Command;
+use crate::answers;
+use crate::backend::{
+    self, ConfirmRequest, DateRequest, EditorRequest, MultiSelectRequest, NumberRequest, Secret,
+    SelectRequest, TextRequest,
+};
+use crate::history::History;
+use crate::menu::{Menu, OnFailure, Shell};
+use crate::rawprompt::{self, render_config, styled};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::{cursor, terminal};
+use custom_error::custom_error;
+use serde_json::{json, Value};
+use std::cell::{Cell, RefCell};
+use std::io::{Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, Instant};
 
 use chrono::{NaiveDate, Weekday};
 use clap::ValueEnum;
+use inquire::error::InquireResult;
 use inquire::{
-    autocompletion::Replacement, error::CustomUserError, Confirm, DateSelect, Editor, InquireError,
-    MultiSelect, Select, Text,
+    autocompletion::Replacement, error::CustomUserError, validator::Validation, InquireError,
 };
+use serde_json::Number;
 
 #[derive(Clone, ValueEnum)]
 pub enum Confirmation {
@@ -13,15 +29,58 @@ pub enum Confirmation {
     No,
 }
 
-fn read_json_array(json: &str) -> Result<Vec<String>, CustomUserError> {
-    let a: Vec<String> = serde_json::from_str(json).expect("invalid json array");
-    Ok(a)
+/// Read a list of options from a file, or from stdin when `path` is
+/// `-`. Options are one per line, or NUL-delimited when `null` is set
+/// (eg. from `find -print0`). Blank entries are skipped.
+pub fn read_options(path: &Path, null: bool) -> std::io::Result<Vec<String>> {
+    let mut buf = Vec::new();
+    if path == Path::new("-") {
+        std::io::stdin().read_to_end(&mut buf)?;
+        attach_stdin_to_tty()?;
+    } else {
+        std::fs::File::open(path)?.read_to_end(&mut buf)?;
+    }
+    let text = String::from_utf8_lossy(&buf);
+    let options = match null {
+        true => text.split('\0').map(String::from).collect::<Vec<_>>(),
+        false => text.lines().map(String::from).collect(),
+    };
+    Ok(options.into_iter().filter(|o| !o.is_empty()).collect())
+}
+
+/// Once stdin has been consumed as data, point it back at the
+/// controlling terminal so the interactive prompt (and any editor it
+/// launches) can still read the keyboard.
+fn attach_stdin_to_tty() -> std::io::Result<()> {
+    #[cfg(unix)]
+    unsafe {
+        let tty_fd = libc::open(c"/dev/tty".as_ptr(), libc::O_RDWR | libc::O_CLOEXEC);
+        if tty_fd < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        let r = libc::dup2(tty_fd, libc::STDIN_FILENO);
+        libc::close(tty_fd);
+        if r < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum PasswordDisplay {
+    /// Show nothing while typing
+    Hidden,
+    /// Show a `*` for each character typed
+    Masked,
+    /// Show the input as typed
+    Full,
 }
 
 #[derive(Clone, Default)]
 pub struct AskAutoCompleter {
     input: String,
-    suggestions_json: String,
+    options: Vec<String>,
     suggestions: Vec<String>,
     suggestion_index: usize,
 }
@@ -41,11 +100,11 @@ impl AskAutoCompleter {
 impl inquire::Autocomplete for AskAutoCompleter {
     fn get_suggestions(&mut self, input: &str) -> Result<Vec<String>, CustomUserError> {
         self.update_input(input)?;
-        self.suggestions = read_json_array(&self.suggestions_json)
-            .expect("Couldn't parse suggestions")
+        self.suggestions = self
+            .options
             .iter()
             .filter(|s| s.to_lowercase().contains(&input.to_lowercase()))
-            .map(|s| String::from(s.clone()))
+            .cloned()
             .collect();
         Ok(self.suggestions.clone())
     }
@@ -59,7 +118,7 @@ impl inquire::Autocomplete for AskAutoCompleter {
         match highlighted_suggestion {
             Some(suggestion) => Ok(Replacement::Some(suggestion)),
             None => {
-                if self.suggestions.len() > 0 {
+                if !self.suggestions.is_empty() {
                     self.suggestion_index = (self.suggestion_index + 1) % self.suggestions.len();
                     Ok(Replacement::Some(
                         self.suggestions
@@ -75,249 +134,1910 @@ impl inquire::Autocomplete for AskAutoCompleter {
     }
 }
 
-pub fn ask_prompt(
-    question: &str,
-    default: &str,
-    allow_blank: bool,
-    suggestions_json: &str,
-    cancel_code: u8,
-) -> String {
-    if question == "" {
-        panic!("Blank question")
-    }
-    let mut auto_completer = AskAutoCompleter::default();
-    auto_completer.suggestions_json = suggestions_json.to_string();
-    match allow_blank {
-        true => {
-            let r: Result<String, InquireError>;
-            match default {
-                "" => {
-                    r = Text::new(question)
-                        .with_autocomplete(auto_completer.clone())
-                        .prompt();
-                }
-                _ => {
-                    r = Text::new(question)
-                        .with_autocomplete(auto_completer.clone())
-                        .with_default(default)
-                        .prompt();
-                }
-            }
-            if r.is_err() {
-                std::process::exit(cancel_code.into());
+/// Expand a leading `~` to the user's home directory.
+fn expand_tilde(input: &str) -> PathBuf {
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+    match (input.strip_prefix('~'), home) {
+        (Some(""), Some(home)) => home,
+        (Some(rest), Some(home)) if rest.starts_with('/') => home.join(&rest[1..]),
+        _ => PathBuf::from(input),
+    }
+}
+
+/// Make `path` absolute and resolve `.` and `..` without touching the
+/// filesystem (the path may not exist yet).
+fn normalize_path(path: &Path) -> PathBuf {
+    let path = match path.is_absolute() {
+        true => path.to_path_buf(),
+        false => std::env::current_dir().unwrap_or_default().join(path),
+    };
+    let mut normal = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normal.pop();
             }
-            r.unwrap()
+            c => normal.push(c),
         }
-        false => {
-            let mut a = String::from("");
-            while a == "" {
-                let r: Result<String, InquireError>;
-                match default {
-                    "" => {
-                        r = Text::new(question)
-                            .with_autocomplete(auto_completer.clone())
-                            .prompt();
+    }
+    normal
+}
+
+/// Express `path` relative to the directory `base`.
+fn relative_path(path: &Path, base: &Path) -> PathBuf {
+    let path = normalize_path(path);
+    let base = normalize_path(base);
+    let common = path
+        .components()
+        .zip(base.components())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let mut relative = PathBuf::new();
+    for _ in base.components().skip(common) {
+        relative.push("..");
+    }
+    for component in path.components().skip(common) {
+        relative.push(component);
+    }
+    match relative.as_os_str().is_empty() {
+        true => PathBuf::from("."),
+        false => relative,
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct PathFilter {
+    pub dir_only: bool,
+    pub file_only: bool,
+    pub glob: Option<glob::Pattern>,
+}
+
+impl PathFilter {
+    /// Whether a file (not a directory) named `name` is acceptable.
+    fn file_matches(&self, name: &str) -> bool {
+        !self.dir_only && self.glob.as_ref().is_none_or(|g| g.matches(name))
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct PathCompleter {
+    filter: PathFilter,
+    show_hidden: bool,
+    suggestions: Vec<String>,
+}
+
+impl PathCompleter {
+    /// Start or stop suggesting hidden files before a `.` is typed.
+    pub(crate) fn toggle_hidden(&mut self) {
+        self.show_hidden = !self.show_hidden;
+    }
+}
+
+impl inquire::Autocomplete for PathCompleter {
+    fn get_suggestions(&mut self, input: &str) -> Result<Vec<String>, CustomUserError> {
+        // Split the input into the directory to list and the partial
+        // name to match, keeping the typed directory text (eg. `~/`) as is:
+        let (typed_dir, prefix) = match input.rfind('/') {
+            Some(i) => input.split_at(i + 1),
+            None => ("", input),
+        };
+        let dir = match typed_dir {
+            "" => PathBuf::from("."),
+            d => expand_tilde(d),
+        };
+        let mut suggestions: Vec<String> = match std::fs::read_dir(dir) {
+            Ok(entries) => entries
+                .filter_map(|e| e.ok())
+                .filter_map(|e| {
+                    let name = e.file_name().to_string_lossy().to_string();
+                    if !name.starts_with(prefix) {
+                        return None;
                     }
-                    _ => {
-                        r = Text::new(question)
-                            .with_default(default)
-                            .with_autocomplete(auto_completer.clone())
-                            .prompt();
+                    // Hidden entries show up when asked for, or once a `.` is typed:
+                    if name.starts_with('.') && !self.show_hidden && !prefix.starts_with('.') {
+                        return None;
                     }
+                    let is_dir = e.path().is_dir();
+                    match is_dir {
+                        true => Some(format!("{}{}/", typed_dir, name)),
+                        false if self.filter.file_matches(&name) => {
+                            Some(format!("{}{}", typed_dir, name))
+                        }
+                        false => None,
+                    }
+                })
+                .collect(),
+            Err(_) => vec![],
+        };
+        suggestions.sort();
+        self.suggestions = suggestions.clone();
+        Ok(suggestions)
+    }
+
+    fn get_completion(
+        &mut self,
+        input: &str,
+        highlighted_suggestion: Option<String>,
+    ) -> Result<Replacement, CustomUserError> {
+        if highlighted_suggestion.is_some() {
+            return Ok(highlighted_suggestion);
+        }
+        // Complete as far as all the suggestions agree:
+        let mut common = match self.suggestions.first() {
+            Some(first) => first.clone(),
+            None => return Ok(Replacement::None),
+        };
+        for s in &self.suggestions[1..] {
+            while !s.starts_with(&common) {
+                common.pop();
+            }
+        }
+        match common.len() > input.len() {
+            true => Ok(Replacement::Some(common)),
+            false => Ok(Replacement::None),
+        }
+    }
+}
+
+/// Exit code used when the `--timeout` runs out with no default answer.
+pub const TIMEOUT_CODE: u8 = 124;
+
+thread_local! {
+    static TIMEOUT: Cell<Option<Duration>> = const { Cell::new(None) };
+}
+
+/// The error for a prompt whose `--timeout` ran out with no default.
+#[derive(Debug)]
+pub struct TimedOut;
+
+impl std::fmt::Display for TimedOut {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Timed out waiting for an answer")
+    }
+}
+
+impl std::error::Error for TimedOut {}
+
+/// Count down `seconds` before each prompt, other than those of a
+/// menu. If no key is pressed before it runs out, the prompt's default
+/// is the answer, or if it has none, the prompt fails with
+/// [`TimedOut`].
+pub fn set_timeout(seconds: u64) {
+    TIMEOUT.set(Some(Duration::from_secs(seconds)));
+}
+
+/// Run `f` with the `--timeout` set to `timeout`, or left as it is if
+/// that's `None`.
+pub fn with_timeout<T>(timeout: Option<Duration>, f: impl FnOnce() -> T) -> T {
+    let outer = TIMEOUT.get();
+    TIMEOUT.set(timeout.or(outer));
+    let result = f();
+    TIMEOUT.set(outer);
+    result
+}
+
+custom_error! {pub WizardError
+    Canceled = "Operation was canceled by the user",
+    Interrupted = "Operation was interrupted by the user",
+    NotTty = "The input device is not a TTY",
+    Io{source:std::io::Error} = "IO error: {source}",
+    InvalidConfiguration{message:String} = "Invalid configuration: {message}",
+    InvalidAnswer{source:answers::InvalidAnswer} = "{source}",
+    TimedOut = "Timed out waiting for an answer",
+    Unsupported{message:String} = "{message}",
+    CommandFailed{code:i32} = "Command failed (exit {code})",
+    Other{message:String} = "{message}",
+}
+
+impl From<InquireError> for WizardError {
+    fn from(e: InquireError) -> Self {
+        match e {
+            InquireError::OperationCanceled => WizardError::Canceled,
+            InquireError::OperationInterrupted => WizardError::Interrupted,
+            InquireError::NotTTY => WizardError::NotTty,
+            InquireError::IO(source) => WizardError::Io { source },
+            InquireError::InvalidConfiguration(message) => {
+                WizardError::InvalidConfiguration { message }
+            }
+            InquireError::Custom(e) if e.is::<TimedOut>() => WizardError::TimedOut,
+            InquireError::Custom(e) => match e.downcast::<answers::InvalidAnswer>() {
+                Ok(source) => WizardError::InvalidAnswer { source: *source },
+                Err(e) => WizardError::Other {
+                    message: e.to_string(),
+                },
+            },
+        }
+    }
+}
+
+/// The error for a builder given options that can't work.
+fn invalid_configuration(message: impl Into<String>) -> InquireError {
+    InquireError::InvalidConfiguration(message.into())
+}
+
+/// How to exit when a prompt fails for a reason other than the user
+/// canceling it with ESC (which uses each subcommand's `--cancel-code`).
+#[derive(Clone, Copy, Default)]
+pub struct ErrorHandling {
+    /// Exit code for Ctrl-C, if not the cancel code
+    pub interrupt_code: Option<u8>,
+    /// Exit code for anything else going wrong, eg. no TTY, if not the
+    /// cancel code
+    pub error_code: Option<u8>,
+    /// Describe the error as JSON on stderr
+    pub json: bool,
+}
+
+thread_local! {
+    static ERROR_HANDLING: Cell<Option<ErrorHandling>> = const { Cell::new(None) };
+}
+
+pub fn set_error_handling(handling: ErrorHandling) {
+    ERROR_HANDLING.set(Some(handling));
+}
+
+/// Run `f` with `handling`. The exit codes it leaves out are kept from
+/// the handling outside.
+pub fn with_error_handling<T>(handling: ErrorHandling, f: impl FnOnce() -> T) -> T {
+    let outer = ERROR_HANDLING.get();
+    let outside = outer.unwrap_or_default();
+    ERROR_HANDLING.set(Some(ErrorHandling {
+        interrupt_code: handling.interrupt_code.or(outside.interrupt_code),
+        error_code: handling.error_code.or(outside.error_code),
+        json: handling.json,
+    }));
+    let result = f();
+    ERROR_HANDLING.set(outer);
+    result
+}
+
+/// A short, stable name for the kind of error `e` is.
+pub fn error_kind(e: &WizardError) -> &'static str {
+    match e {
+        WizardError::Canceled => "canceled",
+        WizardError::Interrupted => "interrupted",
+        WizardError::NotTty => "not-tty",
+        WizardError::Io { .. } => "io",
+        WizardError::InvalidConfiguration { .. } => "invalid-configuration",
+        WizardError::InvalidAnswer { .. } => "invalid-answer",
+        WizardError::TimedOut => "timeout",
+        WizardError::Unsupported { .. } => "unsupported",
+        WizardError::CommandFailed { .. } => "command-failed",
+        WizardError::Other { .. } => "error",
+    }
+}
+
+/// The exit code to use for a prompt that failed with `e`.
+pub fn exit_code(e: &WizardError, cancel_code: u8) -> u8 {
+    exit_code_with(ERROR_HANDLING.get().as_ref(), e, cancel_code)
+}
+
+fn exit_code_with(handling: Option<&ErrorHandling>, e: &WizardError, cancel_code: u8) -> u8 {
+    match e {
+        WizardError::Canceled => cancel_code,
+        WizardError::Interrupted => handling
+            .and_then(|h| h.interrupt_code)
+            .unwrap_or(cancel_code),
+        WizardError::TimedOut => TIMEOUT_CODE,
+        WizardError::CommandFailed { code } => u8::try_from(*code).unwrap_or(1),
+        _ => handling.and_then(|h| h.error_code).unwrap_or(cancel_code),
+    }
+}
+
+/// Describe the error on stderr, and return the exit code to use.
+pub fn report_error(e: &WizardError, cancel_code: u8) -> u8 {
+    let code = exit_code(e, cancel_code);
+    if ERROR_HANDLING.get().is_some_and(|h| h.json) {
+        eprintln!(
+            "{}",
+            serde_json::json!({"error": error_kind(e), "message": e.to_string(), "code": code})
+        );
+    } else if !matches!(e, WizardError::Canceled | WizardError::Interrupted) {
+        eprintln!("{}", e);
+    }
+    code
+}
+
+/// How a countdown ended.
+enum Countdown {
+    /// There is no `--timeout`
+    Off,
+    RanOut,
+    Pressed(KeyEvent),
+}
+
+thread_local! {
+    /// A key typed during a countdown, for the prompt that follows
+    static TYPED_AHEAD: RefCell<Option<char>> = const { RefCell::new(None) };
+}
+
+/// The key typed to stop the countdown, to start the prompt's input
+/// with.
+pub(crate) fn typed_ahead() -> Option<String> {
+    TYPED_AHEAD.take().map(String::from)
+}
+
+/// The `--timeout`, if any.
+pub(crate) fn timeout() -> Option<Duration> {
+    TIMEOUT.get()
+}
+
+/// Show a countdown where the prompt goes until the `--timeout` runs
+/// out or a key is pressed.
+fn count_down(question: &str, default: Option<&Value>) -> InquireResult<Countdown> {
+    let Some(timeout) = TIMEOUT.get() else {
+        return Ok(Countdown::Off);
+    };
+    let shown = match default {
+        Some(Value::String(s)) => format!(" ({})", s),
+        Some(Value::Bool(b)) => format!(" ({})", if *b { "yes" } else { "no" }),
+        Some(v) => format!(" ({})", v),
+        None => String::new(),
+    };
+    let config = render_config();
+    let question = format!(" {}", question);
+    let mut stderr = std::io::stderr();
+    let deadline = Instant::now() + timeout;
+    terminal::enable_raw_mode()?;
+    let ended = loop {
+        let left = deadline.saturating_duration_since(Instant::now());
+        let help = format!(
+            " [{}s left, press any key to answer]",
+            left.as_secs() + u64::from(left.subsec_nanos() > 0)
+        );
+        let _ = write!(
+            stderr,
+            "\r{}",
+            terminal::Clear(terminal::ClearType::CurrentLine)
+        );
+        let parts = [
+            styled(&config.prompt_prefix),
+            (&question, config.prompt),
+            (&shown, config.default_value),
+            (&help, config.help_message),
+        ];
+        let _ = rawprompt::print(&mut stderr, &parts, usize::MAX);
+        let _ = stderr.flush();
+        if left.is_zero() {
+            break Ok(Countdown::RanOut);
+        }
+        match event::poll(left.min(Duration::from_secs(1))) {
+            Ok(true) => match event::read() {
+                Ok(Event::Key(key)) if key.kind != KeyEventKind::Release => {
+                    break Ok(Countdown::Pressed(key))
                 }
-                if r.is_err() {
-                    std::process::exit(cancel_code.into());
-                }
-                a = r.unwrap();
+                Ok(_) => {}
+                Err(e) => break Err(e),
+            },
+            Ok(false) => {}
+            Err(e) => break Err(e),
+        }
+    };
+    let _ = write!(
+        stderr,
+        "\r{}{}",
+        terminal::Clear(terminal::ClearType::CurrentLine),
+        cursor::Show
+    );
+    terminal::disable_raw_mode()?;
+    if let Ok(Countdown::RanOut) = ended {
+        let parts = [
+            styled(&config.answered_prompt_prefix),
+            (&question, config.prompt),
+            (&shown, config.default_value),
+        ];
+        let _ = rawprompt::print(&mut stderr, &parts, usize::MAX);
+        let _ = writeln!(stderr);
+    }
+    Ok(ended?)
+}
+
+/// The answer to give without asking: a canned one (see the
+/// [`answers`] module), or else the `default` if the `--timeout` runs
+/// out first. The key that stops the countdown goes on to the prompt:
+/// enter takes the default, ESC cancels and anything typed starts the
+/// input.
+fn canned_answer(question: &str, default: Option<Value>) -> InquireResult<Option<Value>> {
+    TYPED_AHEAD.take();
+    if let Some(answer) = answers::lookup(question) {
+        return Ok(Some(answer));
+    }
+    match count_down(question, default.as_ref())? {
+        Countdown::Off => Ok(None),
+        Countdown::RanOut => match default {
+            Some(default) => Ok(Some(default)),
+            None => Err(InquireError::Custom(Box::new(TimedOut))),
+        },
+        Countdown::Pressed(key) => match key.code {
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                Err(InquireError::OperationInterrupted)
             }
-            a
+            KeyCode::Esc => Err(InquireError::OperationCanceled),
+            KeyCode::Enter => Ok(default),
+            KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                TYPED_AHEAD.set(Some(c));
+                Ok(None)
+            }
+            _ => Ok(None),
+        },
+    }
+}
+
+/// The answer to a list, which counts down inside the prompt rather
+/// than before it: `default`, shown by its label, if the `--timeout`
+/// ran out.
+fn or_timed_out<T>(
+    answer: InquireResult<T>,
+    question: &str,
+    default: Option<(T, String)>,
+) -> InquireResult<T> {
+    match (answer, default) {
+        (Err(InquireError::Custom(e)), Some((default, label))) if e.is::<TimedOut>() => {
+            let config = render_config();
+            let question = format!(" {} ", question);
+            let parts = [
+                styled(&config.answered_prompt_prefix),
+                (&question, config.prompt),
+                (&label, config.answer),
+            ];
+            let mut stderr = std::io::stderr();
+            let _ = rawprompt::print(&mut stderr, &parts, usize::MAX);
+            let _ = writeln!(stderr);
+            Ok(default)
+        }
+        (answer, _) => answer,
+    }
+}
+
+/// `s` as an answer, unless it's blank.
+fn non_blank(s: &str) -> Option<Value> {
+    match s {
+        "" => None,
+        s => Some(Value::from(s)),
+    }
+}
+
+/// Ask a free-form question, with optional suggestions to complete
+/// from:
+///
+/// ```no_run
+/// # use script_wizard::ask::AskBuilder;
+/// let name = AskBuilder::new("What is your name?").default("World").prompt()?;
+/// # Ok::<(), script_wizard::ask::WizardError>(())
+/// ```
+#[derive(Clone, Default)]
+pub struct AskBuilder {
+    question: String,
+    default: String,
+    allow_blank: bool,
+    suggestions: Vec<String>,
+}
+
+impl AskBuilder {
+    pub fn new(question: &str) -> Self {
+        AskBuilder {
+            question: question.to_string(),
+            ..Default::default()
         }
     }
+
+    pub fn default(mut self, default: &str) -> Self {
+        self.default = default.to_string();
+        self
+    }
+
+    pub fn allow_blank(mut self, allow_blank: bool) -> Self {
+        self.allow_blank = allow_blank;
+        self
+    }
+
+    pub fn suggestions<S: AsRef<str>>(mut self, suggestions: impl IntoIterator<Item = S>) -> Self {
+        self.suggestions = suggestions
+            .into_iter()
+            .map(|s| s.as_ref().to_string())
+            .collect();
+        self
+    }
+
+    pub fn prompt(&self) -> Result<String, WizardError> {
+        Ok(self.try_prompt()?)
+    }
+
+    fn try_prompt(&self) -> InquireResult<String> {
+        let question = self.question.as_str();
+        answers::recorded("ask", question, vec![], || {
+            let default = answers::default_or(&self.default);
+            let default = default.as_str();
+            if question.is_empty() {
+                return Err(invalid_configuration("blank question"));
+            }
+            if let Some(answer) = &canned_answer(question, non_blank(default))? {
+                let text = answers::text(question, answer)?;
+                return match text.is_empty() && !self.allow_blank {
+                    true => Err(answers::invalid(question, answer, "must not be blank")),
+                    false => Ok(text),
+                };
+            }
+            let backend = backend::current();
+            loop {
+                let answer = backend.text(TextRequest {
+                    question,
+                    default: Some(default).filter(|d| !d.is_empty()),
+                    autocomplete: Some(Box::new(AskAutoCompleter {
+                        options: self.suggestions.clone(),
+                        ..Default::default()
+                    })),
+                    ..Default::default()
+                });
+                match answer {
+                    Ok(a) if a.is_empty() && !self.allow_blank => continue,
+                    answer => return answer,
+                }
+            }
+        })
+    }
 }
 
+/// Ask a question, returning the answer or the [`WizardError`] that
+/// stopped it, eg. `ask!("What is your name?", "World")?`.
 #[macro_export]
 macro_rules! ask {
-    ($question: expr, $default: expr, $allow_blank: expr, $suggestions_json: expr, $cancel_code: expr) => {
-        ask::ask_prompt($question, $default, $allow_blank, $suggestions_json, $cancel_code)
-    };
-    ($question: expr, $default: expr, $allow_blank: expr, $suggestions_json: expr) => {
-        ask::ask_prompt($question, $default, $allow_blank, $suggestions_json, 1)
+    ($question: expr, $default: expr, $allow_blank: expr, $suggestions: expr) => {
+        $crate::ask::AskBuilder::new($question)
+            .default($default)
+            .allow_blank($allow_blank)
+            .suggestions($suggestions)
+            .prompt()
     };
     ($question: expr, $default: expr, $allow_blank: expr) => {
-        ask::ask_prompt($question, $default, $allow_blank, "", 1)
+        $crate::ask::AskBuilder::new($question)
+            .default($default)
+            .allow_blank($allow_blank)
+            .prompt()
     };
     ($question: expr, $default: expr) => {
-        ask::ask_prompt($question, $default, false, "", 1)
+        $crate::ask::AskBuilder::new($question)
+            .default($default)
+            .prompt()
     };
     ($question: expr) => {
-        ask::ask_prompt($question, "", false, "", 1)
+        $crate::ask::AskBuilder::new($question).prompt()
     };
 }
 pub use ask;
 
-pub fn confirm(question: &str, default_answer: Option<Confirmation>, cancel_code: u8) -> bool {
-    let mut c = Confirm::new(question);
-    match default_answer {
-        Some(Confirmation::Yes) => c = c.with_default(true),
-        Some(Confirmation::No) => c = c.with_default(false),
-        _ => (),
+/// Ask a yes/no question.
+#[derive(Clone, Default)]
+pub struct ConfirmBuilder {
+    question: String,
+    default: Option<Confirmation>,
+    always_ask: bool,
+}
+
+impl ConfirmBuilder {
+    pub fn new(question: &str) -> Self {
+        ConfirmBuilder {
+            question: question.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn default(mut self, default: Option<Confirmation>) -> Self {
+        self.default = default;
+        self
+    }
+
+    /// Ask even with a canned answer or a `--timeout`: for questions
+    /// that steer a menu, which someone has to be there to answer.
+    pub(crate) fn always_ask(mut self) -> Self {
+        self.always_ask = true;
+        self
+    }
+
+    pub fn prompt(&self) -> Result<bool, WizardError> {
+        Ok(self.try_prompt()?)
     }
-    match c.prompt() {
-        Ok(true) => true,
-        Ok(false) => false,
-        Err(_) => std::process::exit(cancel_code.into()),
+
+    fn try_prompt(&self) -> InquireResult<bool> {
+        let question = self.question.as_str();
+        answers::recorded("confirm", question, vec![], || {
+            let default_answer = match answers::default_or("").to_lowercase().as_str() {
+                "yes" | "y" | "true" => Some(Confirmation::Yes),
+                "no" | "n" | "false" => Some(Confirmation::No),
+                _ => self.default.clone(),
+            };
+            let default_value = default_answer
+                .as_ref()
+                .map(|d| Value::Bool(matches!(d, Confirmation::Yes)));
+            let canned = match self.always_ask {
+                true => None,
+                false => canned_answer(question, default_value)?,
+            };
+            if let Some(answer) = &canned {
+                return match answer {
+                    Value::Bool(b) => Ok(*b),
+                    Value::String(s) => match s.to_lowercase().as_str() {
+                        "yes" | "y" | "true" => Ok(true),
+                        "no" | "n" | "false" => Ok(false),
+                        _ => Err(answers::invalid(question, answer, "expected yes or no")),
+                    },
+                    _ => Err(answers::invalid(question, answer, "expected yes or no")),
+                };
+            }
+            backend::current().confirm(ConfirmRequest {
+                question,
+                default: default_answer.map(|d| matches!(d, Confirmation::Yes)),
+            })
+        })
     }
 }
 
-pub fn choose(
-    question: &str,
-    default: &str,
-    options: Vec<&str>,
-    numeric: &bool,
-    cancel_code: u8,
-) -> String {
-    // Resolve the default to a starting cursor index.
-    //   --numeric mode: default is an index into `options`.
-    //   value mode:     default is matched against option strings.
-    // Always clamp to the valid range so an out-of-bounds default doesn't
-    // cause inquire to Err immediately at startup (previously: default="8"
-    // on a 5-item list crashed the pod without user input, since inquire's
-    // Select rejects with_starting_cursor > options.len()).
-    let raw_index: usize = if *numeric {
-        default.trim().parse::<usize>().unwrap_or(0)
+/// Ask for a secret without echoing it.
+#[derive(Clone)]
+pub struct PasswordBuilder {
+    question: String,
+    display: PasswordDisplay,
+    toggle: bool,
+    confirm: bool,
+    min_length: usize,
+}
+
+impl PasswordBuilder {
+    pub fn new(question: &str) -> Self {
+        PasswordBuilder {
+            question: question.to_string(),
+            display: PasswordDisplay::Masked,
+            toggle: false,
+            confirm: false,
+            min_length: 1,
+        }
+    }
+
+    pub fn display(mut self, display: PasswordDisplay) -> Self {
+        self.display = display;
+        self
+    }
+
+    /// Let Ctrl-R reveal and hide the input.
+    pub fn toggle(mut self, toggle: bool) -> Self {
+        self.toggle = toggle;
+        self
+    }
+
+    /// Ask a second time, re-asking until both entries match.
+    pub fn confirm(mut self, confirm: bool) -> Self {
+        self.confirm = confirm;
+        self
+    }
+
+    pub fn min_length(mut self, min_length: usize) -> Self {
+        self.min_length = min_length;
+        self
+    }
+
+    pub fn prompt(&self) -> Result<String, WizardError> {
+        Ok(self.try_prompt()?)
+    }
+
+    fn try_prompt(&self) -> InquireResult<String> {
+        let question = self.question.as_str();
+        let min_length = self.min_length;
+        answers::recorded_secret("password", question, || {
+            if let Some(answer) = &canned_answer(question, None)? {
+                let secret = answers::text(question, answer)?;
+                return match secret.chars().count() < min_length {
+                    true => Err(answers::invalid(question, answer, "too short")),
+                    false => Ok(secret),
+                };
+            }
+            let validator = move |input: &str| {
+                if input.chars().count() < min_length {
+                    Ok(Validation::Invalid(
+                        format!("Must be at least {} characters long", min_length).into(),
+                    ))
+                } else {
+                    Ok(Validation::Valid)
+                }
+            };
+            backend::current().text(TextRequest {
+                question,
+                help_message: match self.toggle {
+                    true => Some("Ctrl-R to reveal/hide, ESC to cancel"),
+                    false => None,
+                },
+                validator: Some(Box::new(validator)),
+                secret: Some(Secret {
+                    display: self.display,
+                    toggle: self.toggle,
+                    confirm: self.confirm,
+                }),
+                ..Default::default()
+            })
+        })
+    }
+}
+
+/// Parse `input` as an integer (or any finite float, unless `integer`
+/// is set) and check it lies within `min..=max`. Whole numbers are kept
+/// as integers either way.
+pub(crate) fn parse_number(
+    input: &str,
+    integer: bool,
+    min: Option<f64>,
+    max: Option<f64>,
+) -> Result<Number, String> {
+    let input = input.trim();
+    let n = if integer {
+        input
+            .parse::<i64>()
+            .map(Number::from)
+            .map_err(|_| format!("{:?} is not a whole number", input))?
+    } else if let Ok(i) = input.parse::<i64>() {
+        Number::from(i)
     } else {
-        options
-            .iter()
-            .position(|&r| r == default)
-            .unwrap_or(0)
+        input
+            .parse::<f64>()
+            .ok()
+            .and_then(Number::from_f64)
+            .ok_or_else(|| format!("{:?} is not a number", input))?
     };
-    let default_index = raw_index.min(options.len().saturating_sub(1));
-    let ans: Result<&str, InquireError> = Select::new(question, options.clone())
-        .with_starting_cursor(default_index)
-        .with_help_message("up/down to move, enter to select, type to filter, ESC to cancel")
-        .prompt();
-    match ans {
-        Ok(selection) => match numeric {
-            true => {
-                let index = options.iter().position(|&r| r == selection).unwrap();
-                format!("{}", index)
+    check_range(n.as_f64().unwrap_or(f64::NAN), min, max)?;
+    Ok(n)
+}
+
+/// Check `n` is a finite number within `min..=max`.
+pub(crate) fn check_range(n: f64, min: Option<f64>, max: Option<f64>) -> Result<(), String> {
+    match (min, max) {
+        _ if !n.is_finite() => Err(format!("{} is not a number", n)),
+        (Some(min), Some(max)) if n < min || n > max => {
+            Err(format!("Must be between {} and {}", min, max))
+        }
+        (Some(min), _) if n < min => Err(format!("Must be at least {}", min)),
+        (_, Some(max)) if n > max => Err(format!("Must be at most {}", max)),
+        _ => Ok(()),
+    }
+}
+
+/// `n` as an integer if it's a whole number, as [`parse_number`] keeps
+/// them, or with `float`, always as a float (eg. `8080.0`).
+fn normal_number(n: Number, float: bool) -> Number {
+    let f = n.as_f64().unwrap_or(f64::NAN);
+    match float {
+        true => Number::from_f64(f).unwrap_or(n),
+        false if n.is_f64() && f.fract() == 0.0 && f.abs() < i64::MAX as f64 => {
+            Number::from(f as i64)
+        }
+        false => n,
+    }
+}
+
+/// Ask for a number, re-asking until it parses and lies within the
+/// optional range.
+#[derive(Clone, Default)]
+pub struct NumberBuilder {
+    question: String,
+    default: String,
+    integer: bool,
+    float: bool,
+    min: Option<f64>,
+    max: Option<f64>,
+    step: Option<f64>,
+    help_message: String,
+}
+
+impl NumberBuilder {
+    pub fn new(question: &str) -> Self {
+        NumberBuilder {
+            question: question.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn default(mut self, default: &str) -> Self {
+        self.default = default.to_string();
+        self
+    }
+
+    /// Only accept whole numbers.
+    pub fn integer(mut self, integer: bool) -> Self {
+        self.integer = integer;
+        self
+    }
+
+    /// Answer with a float even for a whole number, eg. `8080.0`.
+    pub fn float(mut self, float: bool) -> Self {
+        self.float = float;
+        self
+    }
+
+    pub fn min(mut self, min: Option<f64>) -> Self {
+        self.min = min;
+        self
+    }
+
+    pub fn max(mut self, max: Option<f64>) -> Self {
+        self.max = max;
+        self
+    }
+
+    /// Let the up/down keys add or subtract `step`, which has to be
+    /// positive (and whole, for an integer).
+    pub fn step(mut self, step: Option<f64>) -> Self {
+        self.step = step;
+        self
+    }
+
+    pub fn help_message(mut self, help_message: &str) -> Self {
+        self.help_message = help_message.to_string();
+        self
+    }
+
+    pub fn prompt(&self) -> Result<Number, WizardError> {
+        Ok(self.try_prompt()?)
+    }
+
+    fn try_prompt(&self) -> InquireResult<Number> {
+        match self.step {
+            Some(step) if !(step.is_finite() && step > 0.0) => {
+                return Err(invalid_configuration(format!(
+                    "step {} is not a positive number",
+                    step
+                )))
             }
-            false => String::from(selection),
-        },
-        Err(_) => std::process::exit(cancel_code.into()),
+            Some(step) if self.integer && step.fract() != 0.0 => {
+                return Err(invalid_configuration(format!(
+                    "step {} is not a whole number",
+                    step
+                )))
+            }
+            _ => {}
+        }
+        answers::recorded("number", &self.question, vec![], || {
+            Ok(normal_number(self.ask()?, self.float))
+        })
+    }
+
+    fn ask(&self) -> InquireResult<Number> {
+        let question = self.question.as_str();
+        let (integer, min, max) = (self.integer, self.min, self.max);
+        let default = answers::default_or(&self.default);
+        let default = default.as_str();
+        if let Some(answer) = &canned_answer(question, non_blank(default))? {
+            let text = answers::text(question, answer)?;
+            return parse_number(&text, integer, min, max)
+                .map_err(|e| answers::invalid(question, answer, &e));
+        }
+        let default = match default {
+            "" => None,
+            d => Some(
+                parse_number(d, integer, min, max)
+                    .map_err(|e| invalid_configuration(format!("default: {}", e)))?,
+            ),
+        };
+        let help = match (self.help_message.as_str(), self.step) {
+            ("", Some(step)) => Some(format!("up/down to step by {}, ESC to cancel", step)),
+            ("", None) => None,
+            (h, _) => Some(h.to_string()),
+        };
+        backend::current().number(NumberRequest {
+            question,
+            default,
+            help_message: help.as_deref(),
+            integer,
+            min,
+            max,
+            step: self.step,
+        })
     }
 }
 
-pub fn select(question: &str, default: &str, options: Vec<&str>, cancel_code: u8) -> Vec<String> {
-    let defaults: Vec<&str> = serde_json::from_str(default).unwrap_or(vec![]);
-    let mut default_indices = vec![];
-    for (index, item) in options.iter().enumerate() {
-        match defaults.iter().find(|&r| r == item) {
-            Some(_) => default_indices.append(&mut vec![index]),
-            None => {}
+/// Check a typed path against the `path` prompt's restrictions.
+fn validate_path(
+    input: &str,
+    must_exist: bool,
+    allow_blank: bool,
+    filter: &PathFilter,
+) -> Result<(), &'static str> {
+    if input.is_empty() {
+        return match allow_blank {
+            true => Ok(()),
+            false => Err("A path is required"),
         };
     }
-    let ans = MultiSelect::new(question, options)
-        .with_default(&default_indices)
-        .with_help_message("spacebar: toggle one, right/left: select all/none, type to filter, ESC to cancel")
-        .prompt();
-    match ans {
-        Ok(selection) => selection.iter().map(|&x| x.into()).collect(),
-        Err(_) => std::process::exit(cancel_code.into()),
+    let path = expand_tilde(input);
+    if must_exist && !path.exists() {
+        return Err("No such file or directory");
     }
+    // A path that doesn't exist yet is a directory if it ends with `/`:
+    let is_dir = path.is_dir() || (!path.exists() && input.ends_with('/'));
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    if is_dir && filter.file_only {
+        return Err("Must be a file, not a directory");
+    }
+    if !is_dir && path.exists() && filter.dir_only {
+        return Err("Must be a directory, not a file");
+    }
+    if !is_dir && !filter.dir_only && !filter.file_matches(&name) {
+        return Err("File name doesn't match the required pattern");
+    }
+    Ok(())
 }
 
-pub fn date(
-    question: &str,
-    default: &str,
-    min_date: &str,
-    max_date: &str,
-    starting_date: &str,
-    week_start: Weekday,
-    help_message: &str,
-    date_format: &str,
-    cancel_code: u8,
-) -> String {
-    let mut picker = DateSelect::new(question)
-        .with_starting_date(
-            NaiveDate::parse_from_str(default, date_format)
-                .unwrap_or(chrono::Local::now().naive_local().into()),
-        )
-        .with_min_date(NaiveDate::parse_from_str(min_date, date_format).unwrap_or(NaiveDate::MIN))
-        .with_max_date(NaiveDate::parse_from_str(max_date, date_format).unwrap_or(NaiveDate::MAX))
-        .with_week_start(week_start)
-        .with_help_message(help_message);
-    if let Ok(d) = NaiveDate::parse_from_str(starting_date, date_format) {
-        picker = picker.with_starting_date(d);
-    }
-    match picker.prompt() {
-        Ok(date) => date.format(date_format).to_string(),
-        Err(_) => std::process::exit(cancel_code.into()),
-    }
-}
-
-pub fn editor(message: &str, default: &str, help_message: &str, file_extension: &str, cancel_code: u8) -> String {
-    let ans = Editor::new(message)
-        .with_predefined_text(default)
-        .with_help_message(help_message)
-        .with_file_extension(file_extension)
-        .prompt();
-    match ans {
-        Ok(text) => text,
-        Err(_) => std::process::exit(cancel_code.into()),
-    }
-}
-
-pub fn menu(
-    heading: &str,
-    entries: &Vec<String>,
-    default: &Option<String>,
-    once: &bool,
-    cancel_code: u8,
-) -> Result<usize, u8> {
-    if cfg!(target_os = "windows") {
-        eprintln!("Error: the 'menu' subcommand is not supported on Windows.");
-        eprintln!("Use 'choose' with --numeric to implement your own menu loop.");
-        std::process::exit(1);
-    }
-    let mut new_default: String = default.clone().unwrap_or("".to_string());
-    loop {
-        eprintln!("");
-        let titles: Vec<&str> = entries
-            .iter()
-            .map(|e| e.split(" = ").collect::<Vec<&str>>()[0])
-            .collect();
-        let commands: Vec<&str> = entries
-            .iter()
-            .map(|e| e.split(" = ").collect::<Vec<&str>>()[1])
+/// Ask for a file or directory path, completing names from the
+/// filesystem.
+#[derive(Clone, Default)]
+pub struct PathBuilder {
+    question: String,
+    default: String,
+    must_exist: bool,
+    filter: PathFilter,
+    show_hidden: bool,
+    relative_to: Option<PathBuf>,
+    allow_blank: bool,
+}
+
+impl PathBuilder {
+    pub fn new(question: &str) -> Self {
+        PathBuilder {
+            question: question.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn default(mut self, default: &str) -> Self {
+        self.default = default.to_string();
+        self
+    }
+
+    pub fn must_exist(mut self, must_exist: bool) -> Self {
+        self.must_exist = must_exist;
+        self
+    }
+
+    pub fn filter(mut self, filter: PathFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Suggest hidden files from the start, not just once a `.` is
+    /// typed or Ctrl-T is pressed.
+    pub fn show_hidden(mut self, show_hidden: bool) -> Self {
+        self.show_hidden = show_hidden;
+        self
+    }
+
+    /// Return the answer relative to this directory.
+    pub fn relative_to(mut self, relative_to: Option<&Path>) -> Self {
+        self.relative_to = relative_to.map(Path::to_path_buf);
+        self
+    }
+
+    pub fn allow_blank(mut self, allow_blank: bool) -> Self {
+        self.allow_blank = allow_blank;
+        self
+    }
+
+    pub fn prompt(&self) -> Result<String, WizardError> {
+        Ok(self.try_prompt()?)
+    }
+
+    fn try_prompt(&self) -> InquireResult<String> {
+        let question = self.question.as_str();
+        let (must_exist, allow_blank) = (self.must_exist, self.allow_blank);
+        answers::recorded("path", question, vec![], || {
+            let default = answers::default_or(&self.default);
+            let default = default.as_str();
+            let a = match &canned_answer(question, non_blank(default))? {
+                Some(answer) => {
+                    let text = answers::text(question, answer)?;
+                    validate_path(&text, must_exist, allow_blank, &self.filter)
+                        .map_err(|e| answers::invalid(question, answer, e))?;
+                    text
+                }
+                None => {
+                    let validation_filter = self.filter.clone();
+                    let validator = move |input: &str| match validate_path(
+                        input,
+                        must_exist,
+                        allow_blank,
+                        &validation_filter,
+                    ) {
+                        Ok(()) => Ok(Validation::Valid),
+                        Err(e) => Ok(Validation::Invalid(e.into())),
+                    };
+                    backend::current().text(TextRequest {
+                        question,
+                        default: Some(default).filter(|d| !d.is_empty()),
+                        help_message: Some(
+                            "tab to complete, ctrl-t to show or hide hidden files, ESC to cancel",
+                        ),
+                        paths: Some(PathCompleter {
+                            filter: self.filter.clone(),
+                            show_hidden: self.show_hidden,
+                            ..Default::default()
+                        }),
+                        validator: Some(Box::new(validator)),
+                        ..Default::default()
+                    })?
+                }
+            };
+            if a.is_empty() {
+                return Ok(a);
+            }
+            let path = expand_tilde(&a);
+            Ok(match &self.relative_to {
+                Some(base) => relative_path(&path, &expand_tilde(&base.to_string_lossy())),
+                None => path,
+            }
+            .to_string_lossy()
+            .to_string())
+        })
+    }
+}
+
+/// An option shown to the user by its `label`, which returns its
+/// `value` when chosen.
+#[derive(Clone, Debug, serde::Deserialize)]
+pub struct Choice {
+    pub label: String,
+    #[serde(default)]
+    value: Option<String>,
+    /// Shown below the list while the option is highlighted
+    #[serde(default)]
+    pub description: Option<String>,
+    /// A key shown next to the label: pressing it picks this option,
+    /// or toggles it in a multiselect.
+    #[serde(default)]
+    pub hotkey: Option<char>,
+    /// Shown in the list, like a heading, but it can't be chosen
+    #[serde(skip)]
+    pub disabled: bool,
+}
+
+impl Choice {
+    pub fn new(label: &str, value: &str) -> Self {
+        Choice {
+            label: label.to_string(),
+            value: Some(value.to_string()),
+            description: None,
+            hotkey: None,
+            disabled: false,
+        }
+    }
+
+    /// Split `option` into label and value at the first `separator`,
+    /// or use the whole thing as both if it doesn't contain one.
+    pub fn parse(option: &str, separator: Option<&str>) -> Self {
+        match separator.and_then(|sep| option.split_once(sep)) {
+            Some((label, value)) => Choice::new(label, value),
+            None => Choice::new(option, option),
+        }
+    }
+
+    pub fn value(&self) -> &str {
+        self.value.as_deref().unwrap_or(&self.label)
+    }
+
+    /// Whether `s` picks this option, by its value or label. One that
+    /// is disabled can't be picked.
+    pub(crate) fn matches(&self, s: &str) -> bool {
+        !self.disabled && (self.value() == s || self.label == s)
+    }
+}
+
+impl std::fmt::Display for Choice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.label)
+    }
+}
+
+fn choice_values(choices: &[Choice]) -> Vec<String> {
+    choices.iter().map(|c| c.value().to_string()).collect()
+}
+
+/// Plain options, each its own label and value.
+fn plain_choices<S: AsRef<str>>(options: impl IntoIterator<Item = S>) -> Vec<Choice> {
+    options
+        .into_iter()
+        .map(|o| Choice::new(o.as_ref(), o.as_ref()))
+        .collect()
+}
+
+/// Parse a JSON array of `{"label", "value", "description"}` objects,
+/// where only `label` is required.
+pub fn read_json_choices(json: &str) -> Result<Vec<Choice>, serde_json::Error> {
+    serde_json::from_str(json)
+}
+
+/// Choose a single option from a list:
+///
+/// ```no_run
+/// # use script_wizard::ask::ChooseBuilder;
+/// let class = ChooseBuilder::new("Select your character class")
+///     .options(["Rogue", "Wizard", "Paladin"])
+///     .default("Wizard")
+///     .prompt()?;
+/// # Ok::<(), script_wizard::ask::WizardError>(())
+/// ```
+#[derive(Clone, Default)]
+pub struct ChooseBuilder {
+    question: String,
+    default: String,
+    choices: Vec<Choice>,
+    numeric: bool,
+    always_ask: bool,
+}
+
+impl ChooseBuilder {
+    pub fn new(question: &str) -> Self {
+        ChooseBuilder {
+            question: question.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn options<S: AsRef<str>>(mut self, options: impl IntoIterator<Item = S>) -> Self {
+        self.choices = plain_choices(options);
+        self
+    }
+
+    /// Options with labels that differ from their values.
+    pub fn choices(mut self, choices: Vec<Choice>) -> Self {
+        self.choices = choices;
+        self
+    }
+
+    /// The value (or label) of the option to start on, or its index
+    /// when [`numeric`](Self::numeric).
+    pub fn default(mut self, default: &str) -> Self {
+        self.default = default.to_string();
+        self
+    }
+
+    /// Return the index of the chosen option instead of its value.
+    pub fn numeric(mut self, numeric: bool) -> Self {
+        self.numeric = numeric;
+        self
+    }
+
+    /// Ask even with a canned answer or a `--timeout`, as for a menu.
+    pub(crate) fn always_ask(mut self) -> Self {
+        self.always_ask = true;
+        self
+    }
+
+    pub fn prompt(&self) -> Result<String, WizardError> {
+        Ok(self.try_prompt()?)
+    }
+
+    fn try_prompt(&self) -> InquireResult<String> {
+        let question = self.question.as_str();
+        let numeric = self.numeric;
+        let choices = &self.choices;
+        answers::recorded("choose", question, choice_values(choices), || {
+            let default = answers::default_or(&self.default);
+            let default = default.as_str();
+            let canned = match self.always_ask {
+                true => None,
+                false => answers::lookup(question),
+            };
+            if let Some(answer) = &canned {
+                let text = answers::text(question, answer)?;
+                let index = choices
+                    .iter()
+                    .position(|c| c.matches(&text))
+                    .or_else(|| match numeric {
+                        true => text.parse::<usize>().ok().filter(|&i| i < choices.len()),
+                        false => None,
+                    })
+                    .ok_or_else(|| answers::invalid(question, answer, "not one of the options"))?;
+                return Ok(match numeric {
+                    true => format!("{}", index),
+                    false => choices[index].value().to_string(),
+                });
+            }
+            // Resolve the default to a starting cursor index.
+            //   --numeric mode: default is an index into `options`.
+            //   value mode:     default is matched against option values or labels.
+            // Always clamp to the valid range so an out-of-bounds default doesn't
+            // cause inquire to Err immediately at startup (previously: default="8"
+            // on a 5-item list crashed the pod without user input, since inquire's
+            // Select rejects with_starting_cursor > options.len()).
+            let raw_index: Option<usize> = if numeric {
+                default.trim().parse::<usize>().ok()
+            } else {
+                choices.iter().position(|c| c.matches(default))
+            };
+            let default_index = raw_index.unwrap_or(0).min(choices.len().saturating_sub(1));
+            let answer = backend::current().select(SelectRequest {
+                question,
+                options: choices,
+                starting_cursor: default_index,
+                timeout: timeout().filter(|_| !self.always_ask),
+                help_message: Some(match choices.iter().any(|c| c.hotkey.is_some()) {
+                    true => concat!(
+                        "up/down to move, enter or an option's key to select, ",
+                        "/ to filter, ESC to cancel"
+                    ),
+                    false => "up/down to move, enter to select, type to filter, ESC to cancel",
+                }),
+            });
+            let default = raw_index
+                .filter(|_| !choices.is_empty())
+                .map(|_| (default_index, choices[default_index].label.clone()));
+            let index = or_timed_out(answer, question, default)?;
+            Ok(match numeric {
+                true => format!("{}", index),
+                false => choices[index].value().to_string(),
+            })
+        })
+    }
+}
+
+/// Select any number of options from a list.
+#[derive(Clone, Default)]
+pub struct SelectBuilder {
+    question: String,
+    defaults: Vec<String>,
+    choices: Vec<Choice>,
+}
+
+impl SelectBuilder {
+    pub fn new(question: &str) -> Self {
+        SelectBuilder {
+            question: question.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn options<S: AsRef<str>>(mut self, options: impl IntoIterator<Item = S>) -> Self {
+        self.choices = plain_choices(options);
+        self
+    }
+
+    /// Options with labels that differ from their values.
+    pub fn choices(mut self, choices: Vec<Choice>) -> Self {
+        self.choices = choices;
+        self
+    }
+
+    /// The values (or labels) of the options to start out selected.
+    pub fn defaults<S: AsRef<str>>(mut self, defaults: impl IntoIterator<Item = S>) -> Self {
+        self.defaults = defaults
+            .into_iter()
+            .map(|d| d.as_ref().to_string())
             .collect();
-        let command_index = choose(heading, new_default.as_str(), titles, &true, cancel_code)
-            .parse::<usize>()
-            .unwrap_or(1);
-
-        new_default = command_index.to_string();
-
-        // Run the command:
-        let cmd = commands[command_index];
-        let status = Command::new("/bin/bash")
-            .args(["-c", cmd])
-            .status()
-            .unwrap();
-
-        match status.code().unwrap_or(1) {
-            0 => {
-                //Keep looping unless --once is given:
-                if *once {
-                    return Ok(0);
+        self
+    }
+
+    pub fn prompt(&self) -> Result<Vec<String>, WizardError> {
+        Ok(self.try_prompt()?)
+    }
+
+    fn try_prompt(&self) -> InquireResult<Vec<String>> {
+        let question = self.question.as_str();
+        let choices = &self.choices;
+        answers::recorded("select", question, choice_values(choices), || {
+            let default = match self.defaults.is_empty() {
+                true => answers::default_or(""),
+                false => answers::default_or(&json!(self.defaults).to_string()),
+            };
+            let default = default.as_str();
+            if let Some(answer) = &answers::lookup(question) {
+                // Answers from the environment are a JSON array in a string:
+                let parsed: Option<Value> =
+                    answer.as_str().and_then(|s| serde_json::from_str(s).ok());
+                let items = parsed
+                    .as_ref()
+                    .unwrap_or(answer)
+                    .as_array()
+                    .ok_or_else(|| answers::invalid(question, answer, "expected a list"))?;
+                return items
+                    .iter()
+                    .map(|item| {
+                        let text = answers::text(question, item)?;
+                        choices
+                            .iter()
+                            .find(|c| c.matches(&text))
+                            .map(|c| c.value().to_string())
+                            .ok_or_else(|| {
+                                answers::invalid(question, item, "not one of the options")
+                            })
+                    })
+                    .collect();
+            }
+            let defaults: Vec<&str> = serde_json::from_str(default).unwrap_or(vec![]);
+            let mut default_indices = vec![];
+            for (index, item) in choices.iter().enumerate() {
+                if defaults.iter().any(|d| item.matches(d)) {
+                    default_indices.push(index);
                 }
             }
-            2 => {
-                // Ok(2) signals to quit the loop:
-                return Ok(2);
+            let answer = backend::current().multiselect(MultiSelectRequest {
+                question,
+                options: choices,
+                defaults: &default_indices,
+                timeout: timeout(),
+                help_message: Some(match choices.iter().any(|c| c.hotkey.is_some()) {
+                    true => concat!(
+                        "spacebar or an option's key: toggle one, ",
+                        "right/left: select all/none, / to filter, ESC to cancel"
+                    ),
+                    false => concat!(
+                        "spacebar: toggle one, right/left: select all/none, ",
+                        "type to filter, ESC to cancel"
+                    ),
+                }),
+            });
+            let labels: Vec<&str> = default_indices
+                .iter()
+                .map(|&i| choices[i].label.as_str())
+                .collect();
+            let default = non_blank(default).map(|_| (default_indices.clone(), labels.join(", ")));
+            let selection = or_timed_out(answer, question, default)?;
+            Ok(selection
+                .iter()
+                .map(|&i| choices[i].value().to_string())
+                .collect())
+        })
+    }
+}
+
+/// Pick a date from a calendar. Dates given to and returned by the
+/// builder are formatted with [`format`](Self::format).
+#[derive(Clone)]
+pub struct DateBuilder {
+    question: String,
+    default: String,
+    min_date: String,
+    max_date: String,
+    starting_date: String,
+    week_start: Weekday,
+    help_message: String,
+    format: String,
+}
+
+impl DateBuilder {
+    pub fn new(question: &str) -> Self {
+        DateBuilder {
+            question: question.to_string(),
+            default: String::new(),
+            min_date: String::new(),
+            max_date: String::new(),
+            starting_date: String::new(),
+            week_start: Weekday::Sun,
+            help_message: String::new(),
+            format: "%Y-%m-%d".to_string(),
+        }
+    }
+
+    pub fn default(mut self, default: &str) -> Self {
+        self.default = default.to_string();
+        self
+    }
+
+    pub fn min_date(mut self, min_date: &str) -> Self {
+        self.min_date = min_date.to_string();
+        self
+    }
+
+    pub fn max_date(mut self, max_date: &str) -> Self {
+        self.max_date = max_date.to_string();
+        self
+    }
+
+    pub fn starting_date(mut self, starting_date: &str) -> Self {
+        self.starting_date = starting_date.to_string();
+        self
+    }
+
+    pub fn week_start(mut self, week_start: Weekday) -> Self {
+        self.week_start = week_start;
+        self
+    }
+
+    pub fn help_message(mut self, help_message: &str) -> Self {
+        self.help_message = help_message.to_string();
+        self
+    }
+
+    /// A [`chrono` format string](chrono::format::strftime).
+    pub fn format(mut self, format: &str) -> Self {
+        self.format = format.to_string();
+        self
+    }
+
+    pub fn prompt(&self) -> Result<String, WizardError> {
+        Ok(self.try_prompt()?)
+    }
+
+    fn try_prompt(&self) -> InquireResult<String> {
+        let question = self.question.as_str();
+        let date_format = self.format.as_str();
+        answers::recorded("date", question, vec![], || {
+            let default = answers::default_or(&self.default);
+            let default = default.as_str();
+            let min =
+                NaiveDate::parse_from_str(&self.min_date, date_format).unwrap_or(NaiveDate::MIN);
+            let max =
+                NaiveDate::parse_from_str(&self.max_date, date_format).unwrap_or(NaiveDate::MAX);
+            if let Some(answer) = &canned_answer(question, non_blank(default))? {
+                let text = answers::text(question, answer)?;
+                return match NaiveDate::parse_from_str(&text, date_format) {
+                    Ok(d) if d < min || d > max => Err(answers::invalid(
+                        question,
+                        answer,
+                        "outside the allowed dates",
+                    )),
+                    Ok(d) => Ok(d.format(date_format).to_string()),
+                    Err(_) => Err(answers::invalid(
+                        question,
+                        answer,
+                        &format!("expected a date formatted as {}", date_format),
+                    )),
+                };
             }
-            _ => {
-                return Err(1);
+            let starting_date = NaiveDate::parse_from_str(&self.starting_date, date_format)
+                .or_else(|_| NaiveDate::parse_from_str(default, date_format))
+                .unwrap_or(chrono::Local::now().naive_local().into());
+            let date = backend::current().date(DateRequest {
+                question,
+                starting_date,
+                min_date: min,
+                max_date: max,
+                week_start: self.week_start,
+                help_message: Some(&self.help_message),
+            })?;
+            Ok(date.format(date_format).to_string())
+        })
+    }
+}
+
+/// Ask for free-form text in the user's `$EDITOR`.
+#[derive(Clone, Default)]
+pub struct EditorBuilder {
+    message: String,
+    default: String,
+    help_message: String,
+    file_extension: String,
+}
+
+impl EditorBuilder {
+    pub fn new(message: &str) -> Self {
+        EditorBuilder {
+            message: message.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn default(mut self, default: &str) -> Self {
+        self.default = default.to_string();
+        self
+    }
+
+    pub fn help_message(mut self, help_message: &str) -> Self {
+        self.help_message = help_message.to_string();
+        self
+    }
+
+    /// The extension of the temporary file, eg. `.md`, for the editor
+    /// to pick a mode by.
+    pub fn file_extension(mut self, file_extension: &str) -> Self {
+        self.file_extension = file_extension.to_string();
+        self
+    }
+
+    pub fn prompt(&self) -> Result<String, WizardError> {
+        Ok(self.try_prompt()?)
+    }
+
+    fn try_prompt(&self) -> InquireResult<String> {
+        let message = self.message.as_str();
+        answers::recorded("editor", message, vec![], || {
+            let default = answers::default_or(&self.default);
+            let default = default.as_str();
+            if let Some(answer) = &canned_answer(message, non_blank(default))? {
+                return answers::text(message, answer);
             }
+            backend::current().editor(EditorRequest {
+                message,
+                default,
+                help_message: Some(&self.help_message),
+                file_extension: &self.file_extension,
+            })
+        })
+    }
+}
+
+/// How a [`MenuBuilder`] finished.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MenuExit {
+    /// The `once` command finished successfully
+    Done,
+    /// A command exited with code 2, asking to quit the menu
+    Quit,
+}
+
+/// A menu of shell commands, asked in a loop until a command exits
+/// with code 2, or fails.
+#[derive(Clone, Default)]
+pub struct MenuBuilder {
+    heading: String,
+    entries: Vec<String>,
+    menu: Option<Menu>,
+    default: String,
+    once: bool,
+    shell: Option<Shell>,
+    on_failure: Option<OnFailure>,
+    pager: bool,
+    history: bool,
+}
+
+impl MenuBuilder {
+    pub fn new(heading: &str) -> Self {
+        MenuBuilder {
+            heading: heading.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Entries in the form `ENTRY = COMMAND`.
+    pub fn entries<S: AsRef<str>>(mut self, entries: impl IntoIterator<Item = S>) -> Self {
+        self.entries = entries
+            .into_iter()
+            .map(|e| e.as_ref().to_string())
+            .collect();
+        self
+    }
+
+    /// Structured entries, eg. from [`Menu::load`], instead of
+    /// [`entries`](Self::entries). The heading given to
+    /// [`new`](Self::new) replaces the menu's own, unless it's blank.
+    pub fn menu(mut self, menu: Menu) -> Self {
+        self.menu = Some(menu);
+        self
+    }
+
+    /// The index of the entry to start on.
+    pub fn default(mut self, default: &str) -> Self {
+        self.default = default.to_string();
+        self
+    }
+
+    /// Return after the first command finishes.
+    pub fn once(mut self, once: bool) -> Self {
+        self.once = once;
+        self
+    }
+
+    /// How to run the command lines, instead of the menu's own `shell`
+    /// or bash.
+    pub fn shell(mut self, shell: Shell) -> Self {
+        self.shell = Some(shell);
+        self
+    }
+
+    /// What to do when a command fails, instead of the menu's own
+    /// `on_failure` or [`OnFailure::Abort`].
+    pub fn on_failure(mut self, on_failure: OnFailure) -> Self {
+        self.on_failure = Some(on_failure);
+        self
+    }
+
+    /// Show the output of every command in a pager, except for the
+    /// entries that set `pager = false`.
+    pub fn pager(mut self, pager: bool) -> Self {
+        self.pager = pager;
+        self
+    }
+
+    /// Remember the commands run, in `$XDG_STATE_HOME/script-wizard/`,
+    /// to offer them again at the top of the menu next time.
+    pub fn history(mut self, history: bool) -> Self {
+        self.history = history;
+        self
+    }
+
+    pub fn run(&self) -> Result<MenuExit, WizardError> {
+        if cfg!(target_os = "windows") {
+            return Err(WizardError::Unsupported {
+                message: "the 'menu' subcommand is not supported on Windows. \
+                          Use 'choose' with --numeric to implement your own menu loop."
+                    .to_string(),
+            });
+        }
+        let mut menu = match &self.menu {
+            Some(menu) if self.heading.is_empty() => menu.clone(),
+            Some(menu) => Menu {
+                heading: self.heading.clone(),
+                ..menu.clone()
+            },
+            None => Menu::parse_entries(&self.heading, &self.entries)?,
+        };
+        menu.shell = self.shell.or(menu.shell);
+        menu.on_failure = self.on_failure.or(menu.on_failure);
+        menu.pager |= self.pager;
+        let mut history = match self.history {
+            true => History::load(&menu.history_key()),
+            false => History::default(),
+        };
+        menu.run(&self.default, self.once, &mut history)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{with_backend, ScriptedBackend};
+    use std::rc::Rc;
+
+    #[test]
+    fn builders_answer_through_the_backend() {
+        let script = Rc::new(ScriptedBackend::new([
+            json!(""),
+            json!("yes"),
+            json!(1),
+            json!("c"),
+            json!(["a", "c"]),
+            json!("2024-02-29"),
+            json!("Dear Ada,\n"),
+            json!(null),
+        ]));
+        with_backend(script.clone(), || {
+            assert_eq!(
+                AskBuilder::new("Name?").default("World").prompt().unwrap(),
+                "World"
+            );
+            assert!(ConfirmBuilder::new("Continue?").prompt().unwrap());
+            let choose = || ChooseBuilder::new("Pick one").options(["a", "b", "c"]);
+            assert_eq!(choose().prompt().unwrap(), "b");
+            assert_eq!(choose().numeric(true).prompt().unwrap(), "2");
+            let select = SelectBuilder::new("Pick any").options(["a", "b", "c"]);
+            assert_eq!(select.prompt().unwrap(), ["a", "c"]);
+            assert_eq!(DateBuilder::new("When?").prompt().unwrap(), "2024-02-29");
+            assert_eq!(
+                EditorBuilder::new("Letter").prompt().unwrap(),
+                "Dear Ada,\n"
+            );
+
+            // Failures come back as errors rather than exiting:
+            assert!(matches!(
+                AskBuilder::new("Name?").prompt(),
+                Err(WizardError::Canceled)
+            ));
+            let unanswered = ConfirmBuilder::new("Again?").prompt();
+            assert!(matches!(
+                unanswered,
+                Err(WizardError::InvalidConfiguration { .. })
+            ));
+        });
+        assert_eq!(script.remaining(), 0);
+    }
+
+    #[test]
+    fn checks_numbers_and_steps() {
+        assert_eq!(parse_number("42", true, None, None), Ok(Number::from(42)));
+        assert!(parse_number("4.5", true, None, None).is_err());
+        assert_eq!(
+            parse_number(" 4.5 ", false, None, None).unwrap().as_f64(),
+            Some(4.5)
+        );
+        assert!(parse_number("11", false, Some(1.0), Some(10.0)).is_err());
+
+        let script = Rc::new(ScriptedBackend::new([json!("6")]));
+        let number = || NumberBuilder::new("How many?").integer(true);
+        let n = with_backend(script, || number().step(Some(2.0)).prompt()).unwrap();
+        assert_eq!(n, Number::from(6));
+        for step in [0.5, 0.0, -1.0, f64::NAN] {
+            let checked = number().step(Some(step)).prompt();
+            assert!(matches!(
+                checked,
+                Err(WizardError::InvalidConfiguration { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn answers_floats_only_with_float() {
+        let ask = |answer: Value, number: NumberBuilder| {
+            let script = Rc::new(ScriptedBackend::new([answer]));
+            with_backend(script, || number.prompt())
+        };
+        let number = || {
+            NumberBuilder::new("How much?")
+                .default("2")
+                .max(Some(100.0))
+        };
+        assert_eq!(
+            ask(json!("8080.0"), NumberBuilder::new("Port?"))
+                .unwrap()
+                .to_string(),
+            "8080"
+        );
+        assert_eq!(ask(json!(""), number()).unwrap().to_string(), "2");
+        assert_eq!(
+            ask(json!(""), number().float(true)).unwrap().to_string(),
+            "2.0"
+        );
+        assert_eq!(
+            ask(json!(4.5), number().float(true)).unwrap().to_string(),
+            "4.5"
+        );
+        let out_of_range = ask(json!(101), number());
+        assert!(matches!(
+            out_of_range,
+            Err(WizardError::InvalidAnswer { .. })
+        ));
+        assert!(check_range(f64::INFINITY, None, None).is_err());
+    }
+
+    #[test]
+    fn exits_with_the_cancel_code_unless_told_otherwise() {
+        let errors = [
+            WizardError::Canceled,
+            WizardError::Interrupted,
+            WizardError::NotTty,
+            WizardError::InvalidConfiguration {
+                message: "no options".to_string(),
+            },
+        ];
+        for e in &errors {
+            assert_eq!(exit_code_with(None, e, 1), 1);
         }
+        let handling = ErrorHandling {
+            interrupt_code: Some(130),
+            error_code: Some(3),
+            json: false,
+        };
+        let codes: Vec<u8> = errors
+            .iter()
+            .map(|e| exit_code_with(Some(&handling), e, 1))
+            .collect();
+        assert_eq!(codes, [1, 130, 3, 3]);
+
+        assert_eq!(
+            exit_code_with(None, &WizardError::TimedOut, 1),
+            TIMEOUT_CODE
+        );
+        assert_eq!(
+            exit_code_with(None, &WizardError::CommandFailed { code: 7 }, 0),
+            7
+        );
+        assert_eq!(
+            exit_code_with(None, &WizardError::CommandFailed { code: 300 }, 0),
+            1
+        );
+    }
+
+    #[test]
+    fn reads_options_by_line_or_nul() {
+        let path =
+            std::env::temp_dir().join(format!("script-wizard-options-{}", std::process::id()));
+        std::fs::write(&path, "red\n\ngreen apple\r\nblue\n").unwrap();
+        assert_eq!(
+            read_options(&path, false).unwrap(),
+            ["red", "green apple", "blue"]
+        );
+        std::fs::write(&path, "a\nb\0\0c d\0").unwrap();
+        assert_eq!(read_options(&path, true).unwrap(), ["a\nb", "c d"]);
+        std::fs::remove_file(&path).unwrap();
+        assert!(read_options(&path, false).is_err());
+    }
+
+    #[test]
+    fn checks_password_length() {
+        let ask = |answer: &str| {
+            let script = Rc::new(ScriptedBackend::new([json!(answer)]));
+            with_backend(script, || {
+                PasswordBuilder::new("Password?").min_length(6).prompt()
+            })
+        };
+        assert_eq!(ask("hunter2").unwrap(), "hunter2");
+        assert!(matches!(
+            ask("hunt"),
+            Err(WizardError::InvalidAnswer { .. })
+        ));
+
+        std::env::set_var("SCRIPT_WIZARD_ANSWER_CHECKS_PASSWORD_LENGTH", "abc");
+        let canned = answers::with_key("checks-password-length", || {
+            PasswordBuilder::new("Password?").min_length(6).prompt()
+        });
+        std::env::remove_var("SCRIPT_WIZARD_ANSWER_CHECKS_PASSWORD_LENGTH");
+        assert!(matches!(canned, Err(WizardError::InvalidAnswer { .. })));
+    }
+
+    #[test]
+    fn resolves_paths_without_touching_the_filesystem() {
+        assert_eq!(
+            normalize_path(Path::new("/a/b/../c/./d")),
+            Path::new("/a/c/d")
+        );
+        assert_eq!(normalize_path(Path::new("/a/../../b")), Path::new("/b"));
+        let cwd = std::env::current_dir().unwrap();
+        assert_eq!(normalize_path(Path::new("x/../y")), cwd.join("y"));
+
+        let relative = |path: &str, base: &str| relative_path(Path::new(path), Path::new(base));
+        assert_eq!(
+            relative("/srv/app/conf/x.toml", "/srv/app"),
+            Path::new("conf/x.toml")
+        );
+        assert_eq!(relative("/srv/app", "/srv/app/"), Path::new("."));
+        assert_eq!(
+            relative("/srv/app/../other/x", "/srv/app"),
+            Path::new("../other/x")
+        );
+        // Outside the base, climbing as far as the common root:
+        assert_eq!(
+            relative("/etc/x.toml", "/srv/app"),
+            Path::new("../../etc/x.toml")
+        );
+        assert_eq!(relative("/", "/srv"), Path::new(".."));
+    }
+
+    #[test]
+    fn checks_paths() {
+        let dir = std::env::temp_dir().join(format!("script-wizard-paths-{}", std::process::id()));
+        // Left over if an earlier run of the test failed:
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("conf")).unwrap();
+        std::fs::write(dir.join("app.toml"), "").unwrap();
+        std::os::unix::fs::symlink(dir.join("conf"), dir.join("conf-link")).unwrap();
+        std::os::unix::fs::symlink(dir.join("app.toml"), dir.join("app-link.toml")).unwrap();
+        std::os::unix::fs::symlink(dir.join("gone"), dir.join("dangling")).unwrap();
+        let path = |name: &str| format!("{}/{}", dir.display(), name);
+        let any = PathFilter::default();
+        let dir_only = PathFilter {
+            dir_only: true,
+            ..Default::default()
+        };
+        let file_only = PathFilter {
+            file_only: true,
+            ..Default::default()
+        };
+        let toml = PathFilter {
+            glob: glob::Pattern::new("*.toml").ok(),
+            ..Default::default()
+        };
+
+        assert_eq!(validate_path("", false, true, &any), Ok(()));
+        assert_eq!(
+            validate_path("", false, false, &any),
+            Err("A path is required")
+        );
+        assert_eq!(
+            validate_path(&path("new.toml"), false, false, &toml),
+            Ok(())
+        );
+        assert!(validate_path(&path("new.toml"), true, false, &any).is_err());
+        assert!(validate_path(&path("new.txt"), false, false, &toml).is_err());
+        assert_eq!(
+            validate_path(&path("new/"), false, false, &dir_only),
+            Ok(())
+        );
+        assert!(validate_path(&path("new/"), false, false, &file_only).is_err());
+        assert!(validate_path(&path("conf"), true, false, &file_only).is_err());
+        assert!(validate_path(&path("app.toml"), true, false, &dir_only).is_err());
+        assert_eq!(
+            validate_path(&path("conf/../app.toml"), true, false, &toml),
+            Ok(())
+        );
+        // Symlinks count as what they point to:
+        assert_eq!(
+            validate_path(&path("conf-link"), true, false, &dir_only),
+            Ok(())
+        );
+        assert_eq!(
+            validate_path(&path("app-link.toml"), true, false, &file_only),
+            Ok(())
+        );
+        assert!(validate_path(&path("dangling"), true, false, &any).is_err());
+        // ...and are kept as typed when made relative:
+        let link = relative_path(Path::new(&path("conf-link/x.toml")), &dir);
+        assert_eq!(link, Path::new("conf-link/x.toml"));
+        std::fs::remove_dir_all(dir).unwrap();
     }
 }
@@ -0,0 +1,705 @@
+use crate::answers;
+use crate::ask::{self, Choice, PasswordDisplay, PathCompleter};
+use crate::pager;
+use crate::pathinput::PathInput;
+use crate::picker::Picker;
+use crate::stepper::Stepper;
+use chrono::{NaiveDate, Weekday};
+use inquire::autocompletion::Replacement;
+use inquire::error::{CustomUserError, InquireResult};
+use inquire::validator::{ErrorMessage, StringValidator, Validation};
+use inquire::{
+    Autocomplete, Confirm, CustomType, DateSelect, Editor, InquireError, Password,
+    PasswordDisplayMode, Text,
+};
+use serde_json::{Number, Value};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// A free-form text question, optionally hidden like a password.
+#[derive(Default)]
+pub struct TextRequest<'a> {
+    pub question: &'a str,
+    pub default: Option<&'a str>,
+    pub help_message: Option<&'a str>,
+    pub autocomplete: Option<Box<dyn Autocomplete>>,
+    pub validator: Option<Box<dyn StringValidator>>,
+    /// Hide the input, as for a password
+    pub secret: Option<Secret>,
+    /// Suggest paths from the filesystem, letting Ctrl-T show or hide
+    /// hidden files
+    pub paths: Option<PathCompleter>,
+}
+
+/// How to hide the input of a secret [`TextRequest`].
+#[derive(Clone, Copy)]
+pub struct Secret {
+    pub display: PasswordDisplay,
+    /// Let Ctrl-R reveal and hide the input
+    pub toggle: bool,
+    /// Ask a second time, re-asking until both entries match
+    pub confirm: bool,
+}
+
+/// A number, parsed as an `i64` if `integer`, or else an `f64`.
+pub struct NumberRequest<'a> {
+    pub question: &'a str,
+    pub default: Option<Number>,
+    pub help_message: Option<&'a str>,
+    pub integer: bool,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    /// Let the up/down keys add or subtract this much
+    pub step: Option<f64>,
+}
+
+/// How the up/down keys step the number in a [`NumberRequest`].
+#[derive(Clone, Copy, Debug)]
+pub struct Step {
+    /// How much to add or subtract, a whole number if `integer`
+    pub by: f64,
+    pub integer: bool,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+}
+
+impl Step {
+    /// The number in `input` one step up or down, kept within the
+    /// range, or none if `input` isn't a number.
+    pub fn apply(&self, input: &str, up: bool) -> Option<String> {
+        let by = match up {
+            true => self.by,
+            false => -self.by,
+        };
+        if self.integer {
+            let mut n = input.trim().parse::<i64>().ok()?.saturating_add(by as i64);
+            if let Some(min) = self.min {
+                n = n.max(min.ceil() as i64);
+            }
+            if let Some(max) = self.max {
+                n = n.min(max.floor() as i64);
+            }
+            return Some(n.to_string());
+        }
+        let mut n = input.trim().parse::<f64>().ok().filter(|n| n.is_finite())? + by;
+        if let Some(min) = self.min {
+            n = n.max(min);
+        }
+        if let Some(max) = self.max {
+            n = n.min(max);
+        }
+        // Round off the error of adding eg. 0.1 to 0.2:
+        Some(((n * 1e10).round() / 1e10).to_string())
+    }
+}
+
+pub struct ConfirmRequest<'a> {
+    pub question: &'a str,
+    pub default: Option<bool>,
+}
+
+pub struct SelectRequest<'a> {
+    pub question: &'a str,
+    pub options: &'a [Choice],
+    pub starting_cursor: usize,
+    pub help_message: Option<&'a str>,
+    /// Count down inside the prompt, and fail with
+    /// [`TimedOut`](crate::ask::TimedOut) if no key is pressed in time
+    pub timeout: Option<Duration>,
+}
+
+pub struct MultiSelectRequest<'a> {
+    pub question: &'a str,
+    pub options: &'a [Choice],
+    /// The indexes of the options to start out selected
+    pub defaults: &'a [usize],
+    pub help_message: Option<&'a str>,
+    /// As for [`SelectRequest::timeout`]
+    pub timeout: Option<Duration>,
+}
+
+pub struct DateRequest<'a> {
+    pub question: &'a str,
+    pub starting_date: NaiveDate,
+    pub min_date: NaiveDate,
+    pub max_date: NaiveDate,
+    pub week_start: Weekday,
+    pub help_message: Option<&'a str>,
+}
+
+pub struct EditorRequest<'a> {
+    pub message: &'a str,
+    pub default: &'a str,
+    pub help_message: Option<&'a str>,
+    pub file_extension: &'a str,
+}
+
+/// Output to show in a pager before carrying on.
+pub struct PagerRequest<'a> {
+    pub title: &'a str,
+    pub text: &'a str,
+    /// How the command that printed the text exited, eg. `exit 0`
+    pub status: &'a str,
+}
+
+/// Whatever actually asks the user. Every prompt goes through the
+/// current backend (see [`with_backend`]) once any canned answer or
+/// `--timeout` has been dealt with.
+pub trait PromptBackend {
+    fn text(&self, request: TextRequest) -> InquireResult<String>;
+    fn number(&self, request: NumberRequest) -> InquireResult<Number>;
+    fn confirm(&self, request: ConfirmRequest) -> InquireResult<bool>;
+    /// Returns the index of the chosen option.
+    fn select(&self, request: SelectRequest) -> InquireResult<usize>;
+    /// Returns the indexes of the chosen options.
+    fn multiselect(&self, request: MultiSelectRequest) -> InquireResult<Vec<usize>>;
+    fn date(&self, request: DateRequest) -> InquireResult<NaiveDate>;
+    fn editor(&self, request: EditorRequest) -> InquireResult<String>;
+    /// Show the output until the user is done reading it.
+    fn pager(&self, request: PagerRequest) -> InquireResult<()>;
+}
+
+thread_local! {
+    static BACKEND: RefCell<Option<Rc<dyn PromptBackend>>> = const { RefCell::new(None) };
+}
+
+/// Run `f` with every prompt asked through `backend` instead of the
+/// terminal.
+pub fn with_backend<T>(backend: Rc<dyn PromptBackend>, f: impl FnOnce() -> T) -> T {
+    let outer = BACKEND.with(|b| b.replace(Some(backend)));
+    let result = f();
+    BACKEND.with(|b| *b.borrow_mut() = outer);
+    result
+}
+
+/// The backend to ask with: the one given to [`with_backend`], or else
+/// the terminal.
+pub(crate) fn current() -> Rc<dyn PromptBackend> {
+    BACKEND
+        .with(|b| b.borrow().clone())
+        .unwrap_or_else(|| Rc::new(InquireBackend))
+}
+
+/// Lets a boxed autocompleter be handed to inquire, which wants one it
+/// can clone.
+#[derive(Clone)]
+struct SharedCompleter(Rc<RefCell<Box<dyn Autocomplete>>>);
+
+impl Autocomplete for SharedCompleter {
+    fn get_suggestions(&mut self, input: &str) -> Result<Vec<String>, CustomUserError> {
+        self.0.borrow_mut().get_suggestions(input)
+    }
+
+    fn get_completion(
+        &mut self,
+        input: &str,
+        highlighted_suggestion: Option<String>,
+    ) -> Result<Replacement, CustomUserError> {
+        self.0
+            .borrow_mut()
+            .get_completion(input, highlighted_suggestion)
+    }
+}
+
+/// Ask for a number with inquire's `CustomType`, re-asking until it
+/// parses as a `T` and lies within the request's range.
+fn custom_number<T>(
+    request: &NumberRequest,
+    default: Option<T>,
+    error_message: &str,
+    as_f64: fn(&T) -> f64,
+) -> InquireResult<T>
+where
+    T: Clone + FromStr + ToString + 'static,
+{
+    let (min, max) = (request.min, request.max);
+    let parser = |input: &str| input.trim().parse::<T>().map_err(|_| ());
+    let typed = ask::typed_ahead();
+    let mut prompt = CustomType::<T>::new(request.question)
+        .with_parser(&parser)
+        .with_error_message(error_message)
+        .with_validator(move |n: &T| match ask::check_range(as_f64(n), min, max) {
+            Ok(()) => Ok(Validation::Valid),
+            Err(e) => Ok(Validation::Invalid(e.into())),
+        });
+    if let Some(typed) = &typed {
+        prompt = prompt.with_starting_input(typed);
+    }
+    if let Some(default) = default {
+        prompt = prompt.with_default(default);
+    }
+    if let Some(help) = request.help_message {
+        prompt = prompt.with_help_message(help);
+    }
+    prompt.prompt()
+}
+
+/// Asks on the terminal with inquire. This is the default backend.
+pub struct InquireBackend;
+
+impl PromptBackend for InquireBackend {
+    fn text(&self, request: TextRequest) -> InquireResult<String> {
+        if let (Some(paths), None) = (request.paths, &request.secret) {
+            return PathInput::new(request.question, paths)
+                .default(request.default)
+                .help(request.help_message)
+                .validator(request.validator)
+                .input(ask::typed_ahead())
+                .prompt();
+        }
+        let validators: Vec<Box<dyn StringValidator>> = request.validator.into_iter().collect();
+        if let Some(secret) = request.secret {
+            let mode = match secret.display {
+                PasswordDisplay::Hidden => PasswordDisplayMode::Hidden,
+                PasswordDisplay::Masked => PasswordDisplayMode::Masked,
+                PasswordDisplay::Full => PasswordDisplayMode::Full,
+            };
+            let mut p = Password::new(request.question)
+                .with_display_mode(mode)
+                .with_custom_confirmation_message("Type it again:")
+                .with_custom_confirmation_error_message("The entries don't match, try again.")
+                .with_validators(&validators);
+            if secret.toggle {
+                p = p.with_display_toggle_enabled();
+            }
+            if !secret.confirm {
+                p = p.without_confirmation();
+            }
+            if let Some(help) = request.help_message {
+                p = p.with_help_message(help);
+            }
+            return p.prompt();
+        }
+        let typed = ask::typed_ahead();
+        let mut text = Text::new(request.question).with_validators(&validators);
+        if let Some(typed) = &typed {
+            text = text.with_initial_value(typed);
+        }
+        if let Some(ac) = request.autocomplete {
+            text = text.with_autocomplete(SharedCompleter(Rc::new(RefCell::new(ac))));
+        }
+        if let Some(default) = request.default {
+            text = text.with_default(default);
+        }
+        if let Some(help) = request.help_message {
+            text = text.with_help_message(help);
+        }
+        text.prompt()
+    }
+
+    fn number(&self, request: NumberRequest) -> InquireResult<Number> {
+        let (integer, min, max) = (request.integer, request.min, request.max);
+        if let Some(by) = request.step {
+            // CustomType has no way to add keys, so stepping takes a
+            // prompt of its own:
+            let validator = move |input: &str| match ask::parse_number(input, integer, min, max) {
+                Ok(_) => Ok(Validation::Valid),
+                Err(e) => Ok(Validation::Invalid(e.into())),
+            };
+            let default = request.default.as_ref().map(Number::to_string);
+            let answer = Stepper::new(
+                request.question,
+                Step {
+                    by,
+                    integer,
+                    min,
+                    max,
+                },
+            )
+            .default(default.as_deref())
+            .help(request.help_message)
+            .validator(Some(Box::new(validator)))
+            .input(ask::typed_ahead())
+            .prompt()?;
+            return ask::parse_number(&answer, integer, min, max)
+                .map_err(|e| InquireError::Custom(e.into()));
+        }
+        match integer {
+            true => {
+                let default = request.default.as_ref().and_then(Number::as_i64);
+                let error = "Please type a whole number";
+                custom_number(&request, default, error, |n| *n as f64).map(Number::from)
+            }
+            false => {
+                let default = request.default.as_ref().and_then(Number::as_f64);
+                let n = custom_number(&request, default, "Please type a number", |n| *n)?;
+                Number::from_f64(n).ok_or_else(|| InquireError::Custom("not a number".into()))
+            }
+        }
+    }
+
+    fn confirm(&self, request: ConfirmRequest) -> InquireResult<bool> {
+        let typed = ask::typed_ahead();
+        let mut c = Confirm::new(request.question);
+        if let Some(typed) = &typed {
+            c = c.with_starting_input(typed);
+        }
+        if let Some(default) = request.default {
+            c = c.with_default(default);
+        }
+        c.prompt()
+    }
+
+    fn select(&self, request: SelectRequest) -> InquireResult<usize> {
+        let picked = Picker::new(request.question, request.options, false)
+            .starting_cursor(request.starting_cursor)
+            .help(request.help_message)
+            .timeout(request.timeout)
+            .prompt()?;
+        Ok(picked[0])
+    }
+
+    fn multiselect(&self, request: MultiSelectRequest) -> InquireResult<Vec<usize>> {
+        Picker::new(request.question, request.options, true)
+            .selected(request.defaults)
+            .help(request.help_message)
+            .timeout(request.timeout)
+            .prompt()
+    }
+
+    fn date(&self, request: DateRequest) -> InquireResult<NaiveDate> {
+        let mut picker = DateSelect::new(request.question)
+            .with_starting_date(request.starting_date)
+            .with_min_date(request.min_date)
+            .with_max_date(request.max_date)
+            .with_week_start(request.week_start);
+        if let Some(help) = request.help_message {
+            picker = picker.with_help_message(help);
+        }
+        picker.prompt()
+    }
+
+    fn editor(&self, request: EditorRequest) -> InquireResult<String> {
+        let mut editor = Editor::new(request.message)
+            .with_predefined_text(request.default)
+            .with_file_extension(request.file_extension);
+        if let Some(help) = request.help_message {
+            editor = editor.with_help_message(help);
+        }
+        editor.prompt()
+    }
+
+    fn pager(&self, request: PagerRequest) -> InquireResult<()> {
+        Ok(pager::show(request.title, request.text, request.status)?)
+    }
+}
+
+/// Answers every prompt from a queue, in order, without a terminal:
+/// for testing code that asks questions.
+///
+/// Answers are JSON values: a string for text and editor prompts (`""`
+/// takes the default), a boolean or `"yes"`/`"no"` for confirm, an
+/// option's value, label or index for select (and a list of them for
+/// multiselect), or `{"key": "d"}` to press an option's hotkey, and a
+/// `YYYY-MM-DD` string for dates. `null` cancels the prompt as if ESC
+/// was pressed.
+///
+/// ```
+/// use script_wizard::ask::ChooseBuilder;
+/// use script_wizard::backend::{with_backend, ScriptedBackend};
+/// use serde_json::json;
+/// use std::rc::Rc;
+///
+/// let script = Rc::new(ScriptedBackend::new([json!("Wizard")]));
+/// let class = with_backend(script.clone(), || {
+///     ChooseBuilder::new("Select your character class")
+///         .options(["Rogue", "Wizard", "Paladin"])
+///         .prompt()
+/// });
+/// assert_eq!(class.unwrap(), "Wizard");
+/// assert_eq!(script.remaining(), 0);
+/// ```
+#[derive(Default)]
+pub struct ScriptedBackend {
+    answers: RefCell<VecDeque<Value>>,
+    paged: RefCell<Vec<(String, String)>>,
+}
+
+impl ScriptedBackend {
+    pub fn new(answers: impl IntoIterator<Item = Value>) -> Self {
+        ScriptedBackend {
+            answers: RefCell::new(answers.into_iter().collect()),
+            ..Default::default()
+        }
+    }
+
+    /// Queue another answer.
+    pub fn push(&self, answer: Value) {
+        self.answers.borrow_mut().push_back(answer);
+    }
+
+    /// The text and status of everything shown in a pager so far.
+    pub fn paged(&self) -> Vec<(String, String)> {
+        self.paged.borrow().clone()
+    }
+
+    /// How many answers are still queued.
+    pub fn remaining(&self) -> usize {
+        self.answers.borrow().len()
+    }
+
+    fn next(&self, question: &str) -> InquireResult<Value> {
+        match self.answers.borrow_mut().pop_front() {
+            Some(Value::Null) => Err(InquireError::OperationCanceled),
+            Some(answer) => Ok(answer),
+            None => Err(InquireError::InvalidConfiguration(format!(
+                "no scripted answer left for {:?}",
+                question
+            ))),
+        }
+    }
+
+    /// The index of the option `answer` picks.
+    fn pick(question: &str, options: &[Choice], answer: &Value) -> InquireResult<usize> {
+        let index = match answer {
+            Value::Number(n) => n
+                .as_u64()
+                .map(|i| i as usize)
+                .filter(|&i| i < options.len()),
+            Value::String(s) => options.iter().position(|c| c.matches(s)),
+            Value::Object(o) => match o.get("key").and_then(Value::as_str) {
+                Some(key) => options
+                    .iter()
+                    .position(|c| c.hotkey.is_some() && key.chars().eq(c.hotkey)),
+                None => None,
+            },
+            _ => None,
+        };
+        index
+            .filter(|&i| !options[i].disabled)
+            .ok_or_else(|| answers::invalid(question, answer, "not one of the options"))
+    }
+}
+
+impl PromptBackend for ScriptedBackend {
+    fn text(&self, request: TextRequest) -> InquireResult<String> {
+        let answer = self.next(request.question)?;
+        let mut text = answers::text(request.question, &answer)?;
+        if let (true, Some(default)) = (text.is_empty(), request.default) {
+            text = default.to_string();
+        }
+        if let Some(validator) = &request.validator {
+            if let Validation::Invalid(e) = validator.validate(&text)? {
+                let reason = match e {
+                    ErrorMessage::Custom(reason) => reason,
+                    ErrorMessage::Default => "invalid input".to_string(),
+                };
+                return Err(answers::invalid(request.question, &answer, &reason));
+            }
+        }
+        Ok(text)
+    }
+
+    fn number(&self, request: NumberRequest) -> InquireResult<Number> {
+        let answer = self.next(request.question)?;
+        let text = answers::text(request.question, &answer)?;
+        match (text.as_str(), request.default) {
+            ("", Some(default)) => Ok(default),
+            (text, _) => ask::parse_number(text, request.integer, request.min, request.max)
+                .map_err(|e| answers::invalid(request.question, &answer, &e)),
+        }
+    }
+
+    fn confirm(&self, request: ConfirmRequest) -> InquireResult<bool> {
+        let answer = self.next(request.question)?;
+        match &answer {
+            Value::Bool(b) => Ok(*b),
+            Value::String(s) => match s.to_lowercase().as_str() {
+                "yes" | "y" | "true" => Ok(true),
+                "no" | "n" | "false" => Ok(false),
+                "" if request.default.is_some() => Ok(request.default.unwrap_or_default()),
+                _ => Err(answers::invalid(
+                    request.question,
+                    &answer,
+                    "expected yes or no",
+                )),
+            },
+            _ => Err(answers::invalid(
+                request.question,
+                &answer,
+                "expected yes or no",
+            )),
+        }
+    }
+
+    fn select(&self, request: SelectRequest) -> InquireResult<usize> {
+        let answer = self.next(request.question)?;
+        Self::pick(request.question, request.options, &answer)
+    }
+
+    fn multiselect(&self, request: MultiSelectRequest) -> InquireResult<Vec<usize>> {
+        let answer = self.next(request.question)?;
+        answer
+            .as_array()
+            .ok_or_else(|| answers::invalid(request.question, &answer, "expected a list"))?
+            .iter()
+            .map(|item| Self::pick(request.question, request.options, item))
+            .collect()
+    }
+
+    fn date(&self, request: DateRequest) -> InquireResult<NaiveDate> {
+        let answer = self.next(request.question)?;
+        let text = answers::text(request.question, &answer)?;
+        match NaiveDate::parse_from_str(&text, "%Y-%m-%d") {
+            Ok(d) if d < request.min_date || d > request.max_date => Err(answers::invalid(
+                request.question,
+                &answer,
+                "outside the allowed dates",
+            )),
+            Ok(d) => Ok(d),
+            Err(_) => Err(answers::invalid(
+                request.question,
+                &answer,
+                "expected a date formatted as YYYY-MM-DD",
+            )),
+        }
+    }
+
+    fn editor(&self, request: EditorRequest) -> InquireResult<String> {
+        let answer = self.next(request.message)?;
+        answers::text(request.message, &answer)
+    }
+
+    fn pager(&self, request: PagerRequest) -> InquireResult<()> {
+        self.paged
+            .borrow_mut()
+            .push((request.text.to_string(), request.status.to_string()));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn text(question: &str) -> InquireResult<String> {
+        current().text(TextRequest {
+            question,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn nests_backends() {
+        let outer = Rc::new(ScriptedBackend::new([json!("outer")]));
+        let inner = Rc::new(ScriptedBackend::new([json!("inner")]));
+        with_backend(outer.clone(), || {
+            assert_eq!(
+                with_backend(inner.clone(), || text("Who?")).unwrap(),
+                "inner"
+            );
+            assert_eq!(text("Who?").unwrap(), "outer");
+        });
+        assert_eq!((outer.remaining(), inner.remaining()), (0, 0));
+    }
+
+    #[test]
+    fn scripts_every_kind_of_prompt() {
+        let mut options = vec![
+            Choice::new("Deploy", "deploy"),
+            Choice::new("Drop tables", "drop"),
+            Choice::new("── Danger ──", ""),
+        ];
+        options[1].hotkey = Some('d');
+        options[2].disabled = true;
+        let script = ScriptedBackend::new([
+            json!("drop"),
+            json!(0),
+            json!({"key": "d"}),
+            json!("── Danger ──"),
+            json!(["Deploy", 1]),
+            json!(""),
+            json!("n"),
+            json!("2024-02-29"),
+            json!("29/02/2024"),
+            json!("12"),
+            json!(null),
+        ]);
+        let select = || {
+            script.select(SelectRequest {
+                question: "Do what?",
+                options: &options,
+                starting_cursor: 0,
+                help_message: None,
+                timeout: None,
+            })
+        };
+        assert_eq!(select().unwrap(), 1);
+        assert_eq!(select().unwrap(), 0);
+        assert_eq!(select().unwrap(), 1);
+        assert!(select().is_err());
+        let picked = script.multiselect(MultiSelectRequest {
+            question: "Do what?",
+            options: &options,
+            defaults: &[],
+            help_message: None,
+            timeout: None,
+        });
+        assert_eq!(picked.unwrap(), [0, 1]);
+
+        let confirm = |default| {
+            script.confirm(ConfirmRequest {
+                question: "Sure?",
+                default,
+            })
+        };
+        assert!(confirm(Some(true)).unwrap());
+        assert!(!confirm(None).unwrap());
+
+        let date = || {
+            script.date(DateRequest {
+                question: "When?",
+                starting_date: NaiveDate::MIN,
+                min_date: NaiveDate::MIN,
+                max_date: NaiveDate::MAX,
+                week_start: Weekday::Mon,
+                help_message: None,
+            })
+        };
+        assert_eq!(
+            date().unwrap(),
+            NaiveDate::from_ymd_opt(2024, 2, 29).unwrap()
+        );
+        assert!(date().is_err());
+
+        let number = script.number(NumberRequest {
+            question: "How many?",
+            default: None,
+            help_message: None,
+            integer: true,
+            min: None,
+            max: Some(10.0),
+            step: None,
+        });
+        assert!(number.is_err());
+
+        let editor = || {
+            script.editor(EditorRequest {
+                message: "Notes",
+                default: "",
+                help_message: None,
+                file_extension: ".txt",
+            })
+        };
+        assert!(matches!(editor(), Err(InquireError::OperationCanceled)));
+        assert!(matches!(
+            editor(),
+            Err(InquireError::InvalidConfiguration(_))
+        ));
+
+        let pager = PagerRequest {
+            title: "ls",
+            text: "Cargo.toml\n",
+            status: "exit 0",
+        };
+        script.pager(pager).unwrap();
+        assert_eq!(
+            script.paged(),
+            [("Cargo.toml\n".to_string(), "exit 0".to_string())]
+        );
+    }
+}
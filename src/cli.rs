@@ -0,0 +1,844 @@
+//! The command line interface: the subcommands, and how their answers
+//! are printed. The Babashka pod runs its invocations through the same
+//! parser.
+
+use crate::{answers, ask, example, form, menu, payload, pod};
+use clap::{Parser, Subcommand};
+use std::io::IsTerminal;
+
+/// The command line, which the pod also parses its invocations with.
+#[derive(Parser)]
+#[command(author, version, about, long_about = None)]
+pub(crate) struct Cli {
+    #[command(subcommand)]
+    pub(crate) command: Option<Commands>,
+    #[arg(
+        long,
+        global = true,
+        env = "SCRIPT_WIZARD_ANSWERS",
+        value_name = "FILE",
+        help = "JSON or TOML file of answers to give instead of asking, keyed by question"
+    )]
+    answers: Option<std::path::PathBuf>,
+    #[arg(
+        long,
+        global = true,
+        env = "SCRIPT_WIZARD_RECORD",
+        value_name = "FILE",
+        help = "Append every answer given to this file as JSON lines, for use with --answers"
+    )]
+    record: Option<std::path::PathBuf>,
+    #[arg(
+        long,
+        global = true,
+        env = "SCRIPT_WIZARD_RECORD_SECRETS",
+        help = "Record password answers in the clear instead of redacting them"
+    )]
+    record_secrets: bool,
+    #[arg(
+        long,
+        global = true,
+        value_name = "NAME",
+        help = concat!(
+            "Name this question: SCRIPT_WIZARD_DEFAULT_<NAME> sets its default, ",
+            "and SCRIPT_WIZARD_ANSWER_<NAME> answers it without asking"
+        )
+    )]
+    key: Option<String>,
+    #[arg(
+        long,
+        global = true,
+        value_name = "SECONDS",
+        help = concat!(
+            "Give up waiting after this long: use the default answer, ",
+            "or exit with code 124 if there is none"
+        )
+    )]
+    timeout: Option<u64>,
+    #[arg(
+        long,
+        global = true,
+        value_name = "CODE",
+        help = "when interrupted with Ctrl-C, use this exit code instead of the cancel code"
+    )]
+    interrupt_code: Option<u8>,
+    #[arg(
+        long,
+        global = true,
+        value_name = "CODE",
+        help = concat!(
+            "when the prompt fails (eg. there is no TTY), ",
+            "use this exit code instead of the cancel code"
+        )
+    )]
+    error_code: Option<u8>,
+}
+
+#[derive(Subcommand)]
+pub(crate) enum Commands {
+    /// Ask an interactive question and output the response
+    Ask {
+        /// The question to ask
+        question: String,
+        default: Option<String>,
+        #[arg(short, long)]
+        json: bool,
+        #[arg(
+            long,
+            help = "Allow the user input to be blank, otherwise re-ask again"
+        )]
+        allow_blank: bool,
+        #[arg(
+            long,
+            value_name = "JSON_ARRAY",
+            help = "JSON serialized array of autocompletion strings to allow",
+            default_value = "[]"
+        )]
+        suggestions: Option<String>,
+        #[arg(short, long, help = "when canceling, use this exit code instead of 1")]
+        cancel_code: Option<u8>,
+    },
+    /// Ask an interactive yes/no question
+    Confirm {
+        /// Ask a yes/no question
+        question: String,
+        /// Default answer yes/no
+        default: Option<ask::Confirmation>,
+        #[arg(short, long, help = "when canceling, use this exit code instead of 1")]
+        cancel_code: Option<u8>,
+    },
+    /// Ask for a secret without echoing it
+    Password {
+        /// The question to ask
+        question: String,
+        #[arg(long, value_name = "MODE", default_value = "masked")]
+        /// How to display the input while typing
+        display: ask::PasswordDisplay,
+        #[arg(long, help = "Allow Ctrl-R to toggle showing the full input")]
+        toggle: bool,
+        #[arg(long, help = "Ask a second time and re-ask if the entries don't match")]
+        confirm: bool,
+        #[arg(long, value_name = "N", default_value = "1")]
+        /// Minimum number of characters required
+        min_length: usize,
+        #[arg(short, long)]
+        json: bool,
+        #[arg(short, long, help = "when canceling, use this exit code instead of 1")]
+        cancel_code: Option<u8>,
+    },
+    /// Ask for a number within an optional range
+    Number {
+        /// The question to ask
+        question: String,
+        default: Option<String>,
+        #[arg(long, value_name = "NUMBER", allow_negative_numbers = true)]
+        /// Smallest number allowed
+        min: Option<f64>,
+        #[arg(long, value_name = "NUMBER", allow_negative_numbers = true)]
+        /// Largest number allowed
+        max: Option<f64>,
+        #[arg(long, conflicts_with = "float")]
+        /// Only accept whole numbers
+        integer: bool,
+        #[arg(long)]
+        /// Answer with a decimal number, even for a whole one (eg. 8080.0)
+        float: bool,
+        #[arg(long, value_name = "NUMBER")]
+        /// Use up/down to add or subtract this amount (positive, and whole
+        /// with --integer)
+        step: Option<f64>,
+        #[arg(long, value_name = "MESSAGE")]
+        help_message: Option<String>,
+        #[arg(short, long)]
+        json: bool,
+        #[arg(short, long, help = "when canceling, use this exit code instead of 1")]
+        cancel_code: Option<u8>,
+    },
+    /// Ask for a file or directory path, with autocompletion
+    Path {
+        /// The question to ask
+        question: String,
+        default: Option<String>,
+        #[arg(long, help = "Only accept paths that already exist")]
+        must_exist: bool,
+        #[arg(long, conflicts_with = "file_only", help = "Only accept directories")]
+        dir_only: bool,
+        #[arg(long, help = "Only accept files")]
+        file_only: bool,
+        #[arg(
+            long,
+            value_name = "PATTERN",
+            help = "Only accept file names matching this glob, eg. '*.toml'"
+        )]
+        glob: Option<String>,
+        #[arg(
+            long,
+            help = "Suggest hidden files from the start (Ctrl-T shows or hides them)"
+        )]
+        hidden: bool,
+        #[arg(
+            long,
+            value_name = "DIR",
+            help = "Print the path relative to this directory"
+        )]
+        relative_to: Option<std::path::PathBuf>,
+        #[arg(
+            long,
+            help = "Allow the user input to be blank, otherwise re-ask again"
+        )]
+        allow_blank: bool,
+        #[arg(short, long)]
+        json: bool,
+        #[arg(short, long, help = "when canceling, use this exit code instead of 1")]
+        cancel_code: Option<u8>,
+    },
+    /// Choose a single item from a list of choices
+    Choose {
+        /// Selection prompt
+        question: String,
+        /// Available choices (use - to read them from stdin)
+        options: Vec<String>,
+        #[arg(long, value_name = "PATH", conflicts_with = "options")]
+        /// Read the choices from a file, one per line
+        options_file: Option<std::path::PathBuf>,
+        #[arg(short = '0', long)]
+        /// Choices read from stdin or a file are NUL-delimited
+        null: bool,
+        #[arg(long, value_name = "SEP")]
+        /// Split each choice into a label to show and a value to print, eg. `--separator =`
+        separator: Option<String>,
+        #[arg(
+            long,
+            value_name = "JSON_ARRAY",
+            conflicts_with_all = ["options", "options_file"],
+            help = "JSON serialized array of {\"label\", \"value\", \"description\"} choices"
+        )]
+        options_json: Option<String>,
+        /// Default answer
+        #[arg(short, long, value_name = "ITEM")]
+        default: Option<String>,
+        #[arg(short, long)]
+        json: bool,
+        #[arg(short, long, help = "return result as numeric value")]
+        numeric: bool,
+        #[arg(short, long, help = "when canceling, use this exit code instead of 1")]
+        cancel_code: Option<u8>,
+    },
+    /// Select multiple items from a list of choices
+    Select {
+        /// Selection prompt
+        question: String,
+        /// Available choices (use - to read them from stdin)
+        options: Vec<String>,
+        #[arg(long, value_name = "PATH", conflicts_with = "options")]
+        /// Read the choices from a file, one per line
+        options_file: Option<std::path::PathBuf>,
+        #[arg(short = '0', long)]
+        /// Choices read from stdin or a file are NUL-delimited
+        null: bool,
+        #[arg(long, value_name = "SEP")]
+        /// Split each choice into a label to show and a value to print, eg. `--separator =`
+        separator: Option<String>,
+        #[arg(
+            long,
+            value_name = "JSON_ARRAY",
+            conflicts_with_all = ["options", "options_file"],
+            help = "JSON serialized array of {\"label\", \"value\", \"description\"} choices"
+        )]
+        options_json: Option<String>,
+        /// Default answer
+        #[arg(short, long, value_name = "JSON_ARRAY")]
+        default: Option<String>,
+        #[arg(short, long)]
+        json: bool,
+        #[arg(short, long, help = "when canceling, use this exit code instead of 1")]
+        cancel_code: Option<u8>,
+    },
+    /// Choose date
+    Date {
+        /// Selection prompt
+        question: String,
+        /// How to format the answer, eg. %d/%m/%Y
+        #[arg(default_value = "%Y-%m-%d", long, value_name = "FORMAT")]
+        format: Option<String>,
+        /// Default answer
+        #[arg(long, value_name = "DATE")]
+        default: Option<String>,
+        #[arg(long, value_name = "DATE")]
+        min_date: Option<String>,
+        #[arg(long, value_name = "DATE")]
+        max_date: Option<String>,
+        #[arg(long, value_name = "DATE")]
+        starting_date: Option<String>,
+        #[arg(default_value = "sunday", long, value_name = "WEEKDAY")]
+        week_start: Option<chrono::Weekday>,
+        #[arg(long, value_name = "MESSAGE")]
+        help_message: Option<String>,
+        #[arg(short, long)]
+        json: bool,
+        #[arg(short, long, help = "when canceling, use this exit code instead of 1")]
+        cancel_code: Option<u8>,
+    },
+    /// Full text editor box
+    Editor {
+        /// The question to ask
+        message: String,
+        #[arg(long, value_name = "TEXT")]
+        default: Option<String>,
+        #[arg(long, value_name = "MESSAGE")]
+        help_message: Option<String>,
+        #[arg(long, value_name = "EXTENSION")]
+        file_extension: Option<String>,
+        #[arg(short, long)]
+        json: bool,
+        #[arg(short, long, help = "when canceling, use this exit code instead of 1")]
+        cancel_code: Option<u8>,
+    },
+    /// Ask a series of questions defined in a TOML or JSON file
+    Form {
+        #[arg(value_name = "SPEC")]
+        /// Form spec file (parsed as JSON if it ends in .json, otherwise TOML)
+        spec: std::path::PathBuf,
+        #[arg(long, value_name = "FORMAT", default_value = "json")]
+        /// How to print the answers
+        output: form::OutputFormat,
+        #[arg(long)]
+        /// Show all the answers at the end, to change any before finishing
+        review: bool,
+        #[arg(short, long, help = "when canceling, use this exit code instead of 1")]
+        cancel_code: Option<u8>,
+    },
+    /// Run external commands from a menu system
+    Menu {
+        #[arg(value_name = "Menu Heading", required_unless_present = "file")]
+        heading: Option<String>,
+        #[arg(value_name = "Entry = command", conflicts_with = "file")]
+        /// List of entries and commands split with " = "
+        entries: Vec<String>,
+        #[arg(long, value_name = "PATH")]
+        /// Read the entries, and any submenus, from a TOML or JSON menu file
+        file: Option<std::path::PathBuf>,
+        #[arg(short, long, value_name = "ENTRY")]
+        /// Default answer
+        default: Option<String>,
+        #[arg(long)]
+        /// Quit after the first command is selected+executed
+        once: bool,
+        #[arg(long, value_enum)]
+        /// How to run the commands, if not with bash or the menu file's shell;
+        /// none runs them without a shell
+        shell: Option<menu::Shell>,
+        #[arg(long, value_enum)]
+        /// When a command fails: abort with its exit code (the default), or
+        /// stay in the menu and offer to retry
+        on_failure: Option<menu::OnFailure>,
+        #[arg(long)]
+        /// Show the output of each command in a scrollable, searchable pager
+        pager: bool,
+        #[arg(long)]
+        /// Don't remember the commands run, or offer them again next time
+        no_history: bool,
+        #[arg(short, long, help = "when canceling, use this exit code instead of 0")]
+        cancel_code: Option<u8>,
+    },
+    /// Show example Bash scripts that use script-wizard
+    Example {
+        /// The example to show
+        name: Option<String>,
+    },
+    /// Run as a Babashka pod (speaks the pod protocol on stdin/stdout)
+    Pod {
+        #[arg(
+            long,
+            value_enum,
+            default_value = "json",
+            env = "SCRIPT_WIZARD_POD_FORMAT",
+            help = "encoding of arguments and results"
+        )]
+        format: payload::Format,
+    },
+}
+
+/// Resolve the choices for `choose`/`select`: given inline, read from
+/// stdin (`-`) or `--options-file`, or parsed from `--options-json`.
+fn load_choices(
+    options: &[String],
+    options_file: &Option<std::path::PathBuf>,
+    null: bool,
+    separator: &Option<String>,
+    options_json: &Option<String>,
+) -> Result<Vec<ask::Choice>, Failure> {
+    if let Some(json) = options_json {
+        return ask::read_json_choices(json)
+            .map_err(|e| Failure::Usage(format!("Invalid --options-json: {}", e)));
+    }
+    let path = match (options_file, options) {
+        (Some(path), _) => Some(path.clone()),
+        (None, [dash]) if dash == "-" => Some(std::path::PathBuf::from("-")),
+        (None, _) => None,
+    };
+    let options = match path {
+        Some(path) => ask::read_options(&path, null).map_err(|e| {
+            Failure::Usage(format!(
+                "Could not read options from {}: {}",
+                path.display(),
+                e
+            ))
+        })?,
+        None => options.to_vec(),
+    };
+    Ok(options
+        .iter()
+        .map(|o| ask::Choice::parse(o, separator.as_deref()))
+        .collect())
+}
+
+/// What a subcommand answered, for the CLI to print or the pod to
+/// return.
+pub(crate) enum Outcome {
+    /// An answer, printed as JSON with `--json`, otherwise as plain text
+    Answer {
+        value: serde_json::Value,
+        json: bool,
+    },
+    /// A yes/no answer, given as the exit code on the command line
+    Confirmed(bool),
+    /// Text to print as is
+    Text(String),
+    /// How a menu finished
+    Menu(ask::MenuExit),
+}
+
+/// Why a subcommand failed.
+pub(crate) enum Failure {
+    /// Bad arguments or input files, with the message to report
+    Usage(String),
+    /// The prompt failed, and `cancel_code` is the exit code for a cancel
+    Prompt {
+        error: ask::WizardError,
+        cancel_code: u8,
+    },
+}
+
+/// The answer to a prompt, or its error with the exit code to use when
+/// it was canceled.
+fn answer<T>(result: Result<T, ask::WizardError>, cancel_code: Option<u8>) -> Result<T, Failure> {
+    result.map_err(|error| Failure::Prompt {
+        error,
+        cancel_code: cancel_code.unwrap_or(1),
+    })
+}
+
+/// An answer from a prompt, to print as text or, with `json`, as JSON.
+fn answered<T: serde::Serialize>(
+    result: Result<T, ask::WizardError>,
+    json: bool,
+    cancel_code: Option<u8>,
+) -> Result<Outcome, Failure> {
+    let value = serde_json::to_value(answer(result, cancel_code)?).unwrap_or_default();
+    Ok(Outcome::Answer { value, json })
+}
+
+/// Parse the command line and run it, returning the exit code.
+pub fn program() -> Result<u8, u8> {
+    let cli = Cli::parse();
+    cli.with_globals(|| run(&cli)).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        Err(1)
+    })
+}
+
+impl Cli {
+    /// Run `f` with the global options: the answers file, the
+    /// recording, the key, the timeout and the exit codes. The pod
+    /// runs each invocation this way, so they can be given to one
+    /// call.
+    pub(crate) fn with_globals<T>(
+        &self,
+        f: impl FnOnce() -> T,
+    ) -> Result<T, answers::AnswersError> {
+        let handling = ask::ErrorHandling {
+            interrupt_code: self.interrupt_code,
+            error_code: self.error_code,
+            json: self.command.as_ref().is_some_and(Commands::json),
+        };
+        let timeout = self.timeout.map(std::time::Duration::from_secs);
+        answers::with_answers(self.answers.as_deref(), || {
+            answers::with_recording(self.record.as_deref(), self.record_secrets, || {
+                ask::with_error_handling(handling, || {
+                    ask::with_timeout(timeout, || match &self.key {
+                        Some(key) => answers::with_key(key, f),
+                        None => f(),
+                    })
+                })
+            })
+        })
+    }
+}
+
+impl Commands {
+    /// Whether --json output was asked for.
+    fn json(&self) -> bool {
+        match self {
+            Commands::Ask { json, .. }
+            | Commands::Password { json, .. }
+            | Commands::Number { json, .. }
+            | Commands::Path { json, .. }
+            | Commands::Choose { json, .. }
+            | Commands::Select { json, .. }
+            | Commands::Date { json, .. }
+            | Commands::Editor { json, .. } => *json,
+            Commands::Form { output, .. } => matches!(output, form::OutputFormat::Json),
+            _ => false,
+        }
+    }
+}
+
+fn run(cli: &Cli) -> Result<u8, u8> {
+    let command = match &cli.command {
+        None => {
+            if std::io::stdin().is_terminal() {
+                // stdin is a TTY and no subcommand given: print help
+                use clap::CommandFactory;
+                Cli::command().print_help().unwrap();
+                eprintln!();
+                return Err(1);
+            } else {
+                // stdin is not a TTY: enter pod mode
+                use clap::ValueEnum;
+                let format = std::env::var("SCRIPT_WIZARD_POD_FORMAT")
+                    .ok()
+                    .and_then(|f| payload::Format::from_str(&f, true).ok())
+                    .unwrap_or_default();
+                pod::run_pod(format);
+                return Ok(0);
+            }
+        }
+        Some(Commands::Pod { format }) => {
+            pod::run_pod(*format);
+            return Ok(0);
+        }
+        Some(command) => command,
+    };
+    match run_command(command) {
+        Ok(Outcome::Answer { value, json: true }) => {
+            println!("{}", value);
+            Ok(0)
+        }
+        Ok(Outcome::Answer { value, json: false }) => {
+            match value {
+                serde_json::Value::String(s) => println!("{}", s),
+                serde_json::Value::Array(items) => {
+                    for item in items {
+                        match item {
+                            serde_json::Value::String(s) => println!("{}", s),
+                            item => println!("{}", item),
+                        }
+                    }
+                }
+                value => println!("{}", value),
+            }
+            Ok(0)
+        }
+        Ok(Outcome::Confirmed(true)) => Ok(0),
+        Ok(Outcome::Confirmed(false)) => Err(1),
+        Ok(Outcome::Text(text)) => {
+            println!("{}", text);
+            Ok(0)
+        }
+        Ok(Outcome::Menu(ask::MenuExit::Done)) => Ok(0),
+        Ok(Outcome::Menu(ask::MenuExit::Quit)) => Err(2),
+        Err(Failure::Usage(message)) => {
+            eprintln!("{}", message);
+            Err(1)
+        }
+        Err(Failure::Prompt {
+            error: error @ ask::WizardError::CommandFailed { .. },
+            ..
+        }) => Err(ask::exit_code(&error, 0)),
+        Err(Failure::Prompt { error, cancel_code }) => Err(ask::report_error(&error, cancel_code)),
+    }
+}
+
+/// Ask the prompt for a subcommand other than `pod`, for the command
+/// line or an invocation from the pod.
+pub(crate) fn run_command(command: &Commands) -> Result<Outcome, Failure> {
+    match command {
+        Commands::Ask {
+            question,
+            default,
+            json,
+            allow_blank,
+            suggestions,
+            cancel_code,
+        } => {
+            let suggestions: Vec<String> = match suggestions.as_deref() {
+                Some(json) => serde_json::from_str(json).map_err(|e| {
+                    Failure::Usage(format!(
+                        "Invalid --suggestions, expected a JSON array of strings: {}",
+                        e
+                    ))
+                })?,
+                None => vec![],
+            };
+            answered(
+                ask::ask!(
+                    question,
+                    default.as_deref().unwrap_or(""),
+                    *allow_blank,
+                    suggestions
+                ),
+                *json,
+                *cancel_code,
+            )
+        }
+        Commands::Confirm {
+            question,
+            default,
+            cancel_code,
+        } => Ok(Outcome::Confirmed(answer(
+            ask::ConfirmBuilder::new(question)
+                .default(default.clone())
+                .prompt(),
+            *cancel_code,
+        )?)),
+        Commands::Password {
+            question,
+            display,
+            toggle,
+            confirm,
+            min_length,
+            json,
+            cancel_code,
+        } => answered(
+            ask::PasswordBuilder::new(question)
+                .display(*display)
+                .toggle(*toggle)
+                .confirm(*confirm)
+                .min_length(*min_length)
+                .prompt(),
+            *json,
+            *cancel_code,
+        ),
+        Commands::Number {
+            question,
+            default,
+            min,
+            max,
+            integer,
+            float,
+            step,
+            help_message,
+            json,
+            cancel_code,
+        } => answered(
+            ask::NumberBuilder::new(question)
+                .default(default.as_deref().unwrap_or(""))
+                .integer(*integer)
+                .float(*float)
+                .min(*min)
+                .max(*max)
+                .step(*step)
+                .help_message(help_message.as_deref().unwrap_or(""))
+                .prompt(),
+            *json,
+            *cancel_code,
+        ),
+        Commands::Path {
+            question,
+            default,
+            must_exist,
+            dir_only,
+            file_only,
+            glob,
+            hidden,
+            relative_to,
+            allow_blank,
+            json,
+            cancel_code,
+        } => {
+            let glob = match glob.as_deref().map(glob::Pattern::new) {
+                Some(Ok(pattern)) => Some(pattern),
+                Some(Err(e)) => {
+                    return Err(Failure::Usage(format!("Invalid --glob pattern: {}", e)))
+                }
+                None => None,
+            };
+            answered(
+                ask::PathBuilder::new(question)
+                    .default(default.as_deref().unwrap_or(""))
+                    .must_exist(*must_exist)
+                    .filter(ask::PathFilter {
+                        dir_only: *dir_only,
+                        file_only: *file_only,
+                        glob,
+                    })
+                    .show_hidden(*hidden)
+                    .relative_to(relative_to.as_deref())
+                    .allow_blank(*allow_blank)
+                    .prompt(),
+                *json,
+                *cancel_code,
+            )
+        }
+        Commands::Choose {
+            question,
+            options,
+            default,
+            options_file,
+            null,
+            separator,
+            options_json,
+            json,
+            numeric,
+            cancel_code,
+        } => {
+            let choices = load_choices(options, options_file, *null, separator, options_json)?;
+            answered(
+                ask::ChooseBuilder::new(question)
+                    .choices(choices)
+                    .default(default.as_deref().unwrap_or(""))
+                    .numeric(*numeric)
+                    .prompt(),
+                *json,
+                *cancel_code,
+            )
+        }
+        Commands::Select {
+            question,
+            options,
+            options_file,
+            null,
+            separator,
+            options_json,
+            default,
+            json,
+            cancel_code,
+        } => {
+            let choices = load_choices(options, options_file, *null, separator, options_json)?;
+            let defaults: Vec<String> = match default.as_deref() {
+                Some(json) => serde_json::from_str(json).map_err(|e| {
+                    Failure::Usage(format!(
+                        "Invalid --default, expected a JSON array of strings: {}",
+                        e
+                    ))
+                })?,
+                None => vec![],
+            };
+            answered(
+                ask::SelectBuilder::new(question)
+                    .choices(choices)
+                    .defaults(defaults)
+                    .prompt(),
+                *json,
+                *cancel_code,
+            )
+        }
+        Commands::Date {
+            question,
+            default,
+            min_date,
+            max_date,
+            starting_date,
+            week_start,
+            help_message,
+            format,
+            json,
+            cancel_code,
+        } => answered(
+            ask::DateBuilder::new(question)
+                .default(default.as_deref().unwrap_or(""))
+                .min_date(min_date.as_deref().unwrap_or(""))
+                .max_date(max_date.as_deref().unwrap_or(""))
+                .starting_date(starting_date.as_deref().unwrap_or(""))
+                .week_start(week_start.unwrap_or(chrono::Weekday::Sun))
+                .help_message(help_message.as_deref().unwrap_or(""))
+                .format(format.as_deref().unwrap_or(""))
+                .prompt(),
+            *json,
+            *cancel_code,
+        ),
+        Commands::Editor {
+            message,
+            default,
+            help_message,
+            file_extension,
+            json,
+            cancel_code,
+        } => answered(
+            ask::EditorBuilder::new(message)
+                .default(default.as_deref().unwrap_or(""))
+                .help_message(help_message.as_deref().unwrap_or(""))
+                .file_extension(file_extension.as_deref().unwrap_or(""))
+                .prompt(),
+            *json,
+            *cancel_code,
+        ),
+        Commands::Form {
+            spec,
+            output,
+            review,
+            cancel_code,
+        } => {
+            let form = form::Form::load(spec).map_err(|e| Failure::Usage(e.to_string()))?;
+            let answers = answer(form.run(*review), *cancel_code)?;
+            match output {
+                form::OutputFormat::Json => Ok(Outcome::Answer {
+                    value: serde_json::Value::Object(answers),
+                    json: true,
+                }),
+                form::OutputFormat::Env => Ok(Outcome::Text(form::to_env(&answers))),
+            }
+        }
+        Commands::Example { name } => {
+            let n = name.clone().unwrap_or("".to_string());
+            match example::example(&n) {
+                Ok(source) => Ok(Outcome::Text(source)),
+                Err(example::ExampleError::Prompt { source }) => Err(Failure::Prompt {
+                    error: source,
+                    cancel_code: 1,
+                }),
+                Err(e) => Err(Failure::Usage(format!("{:?}", e))),
+            }
+        }
+        Commands::Menu {
+            heading,
+            entries,
+            file,
+            default,
+            once,
+            shell,
+            on_failure,
+            pager,
+            no_history,
+            cancel_code,
+        } => {
+            let mut menu = ask::MenuBuilder::new(heading.as_deref().unwrap_or(""))
+                .entries(entries)
+                .default(default.as_deref().unwrap_or(""))
+                .once(*once)
+                .pager(*pager)
+                .history(!*no_history);
+            if let Some(shell) = shell {
+                menu = menu.shell(*shell);
+            }
+            if let Some(on_failure) = on_failure {
+                menu = menu.on_failure(*on_failure);
+            }
+            if let Some(path) = file {
+                menu =
+                    menu.menu(menu::Menu::load(path).map_err(|e| Failure::Usage(e.to_string()))?);
+            }
+            match menu.run() {
+                Ok(exit) => Ok(Outcome::Menu(exit)),
+                Err(error) => Err(Failure::Prompt {
+                    error,
+                    cancel_code: cancel_code.unwrap_or(0),
+                }),
+            }
+        }
+        Commands::Pod { .. } => Err(Failure::Usage(
+            "The pod cannot be run from inside the pod".to_string(),
+        )),
+    }
+}
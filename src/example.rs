@@ -4,9 +4,9 @@ use custom_error::custom_error;
 use std::process::Command;
 use std::str::FromStr;
 use strum::{EnumProperty, IntoEnumIterator};
-use strum_macros;
 custom_error! {pub ExampleError
-    ExampleNotFound{name:String} = "Unknown example: {name}"
+    ExampleNotFound{name:String} = "Unknown example: {name}",
+    Prompt{source:ask::WizardError} = "{source}"
 }
 
 #[derive(
@@ -16,7 +16,6 @@ custom_error! {pub ExampleError
     strum_macros::Display,
     Debug,
 )]
-#[allow(dead_code)]
 enum Example {
     #[strum(props(Name = "Ask into a variable"))]
     Ask,
@@ -42,7 +41,11 @@ pub fn choose_example() -> Result<String, ExampleError> {
     let options: Vec<&str> = Example::iter()
         .map(|e| e.get_str("Name").unwrap())
         .collect();
-    let choice = ask::choose("Choose an example", "", options, &true, 1).parse::<usize>();
+    let choice = ask::ChooseBuilder::new("Choose an example")
+        .options(options)
+        .numeric(true)
+        .prompt()?
+        .parse::<usize>();
     match choice {
         Ok(i) => match Example::iter().nth(i) {
             Some(ex) => example(ex.to_string().as_str()),
@@ -62,7 +65,7 @@ pub fn run_bash(src: &'static str) -> String {
 
 pub fn example(name: &str) -> Result<String, ExampleError> {
     match name {
-        "" => Ok(choose_example().unwrap().to_string()),
+        "" => choose_example(),
         s => match Example::from_str(s) {
             Ok(ex) => match ex {
                 Example::Ask => Ok(run_bash(example_ask())),
@@ -0,0 +1,682 @@
+use crate::answers;
+use crate::ask::{self, WizardError};
+use clap::ValueEnum;
+use custom_error::custom_error;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::path::Path;
+
+custom_error! {pub FormError
+    Read{path:String, source:std::io::Error} = "Could not read form spec {path}: {source}",
+    Toml{source:toml::de::Error} = "Invalid form spec: {source}",
+    Json{source:serde_json::Error} = "Invalid form spec: {source}",
+    Invalid{name:String, reason:String} = "Invalid form field \"{name}\": {reason}",
+}
+
+#[derive(Clone, ValueEnum)]
+pub enum OutputFormat {
+    /// A single JSON object keyed by field name
+    Json,
+    /// `KEY='value'` lines, suitable for `eval`
+    Env,
+}
+
+/// A list of named questions to ask in order.
+#[derive(Deserialize)]
+pub struct Form {
+    #[serde(default)]
+    pub fields: Vec<Field>,
+}
+
+#[derive(Deserialize)]
+pub struct Field {
+    /// The key to store the answer under
+    pub name: String,
+    /// Only ask this field if the condition holds, otherwise leave it
+    /// out of the answers
+    #[serde(default)]
+    pub when: Option<Condition>,
+    #[serde(flatten)]
+    pub prompt: Prompt,
+}
+
+/// A test against the answer of an earlier field. With none of
+/// `equals`, `not-equals` or `one-of` given, the answer must be truthy
+/// (`true`, or a non-empty string or list).
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Condition {
+    pub field: String,
+    pub equals: Option<Value>,
+    pub not_equals: Option<Value>,
+    pub one_of: Option<Vec<Value>>,
+}
+
+impl Condition {
+    pub fn matches(&self, answers: &Map<String, Value>) -> bool {
+        let answer = answers.get(&self.field).unwrap_or(&Value::Null);
+        match (&self.equals, &self.not_equals, &self.one_of) {
+            (Some(v), _, _) => answer == v,
+            (_, Some(v), _) => answer != v,
+            (_, _, Some(vs)) => vs.contains(answer),
+            _ => match answer {
+                Value::Bool(b) => *b,
+                Value::String(s) => !s.is_empty(),
+                Value::Array(a) => !a.is_empty(),
+                Value::Null => false,
+                _ => true,
+            },
+        }
+    }
+}
+
+/// An option given either as a plain string, or as a label/value object.
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum ChoiceSpec {
+    Plain(String),
+    Full(ask::Choice),
+}
+
+impl ChoiceSpec {
+    fn to_choice(&self) -> ask::Choice {
+        match self {
+            ChoiceSpec::Plain(s) => ask::Choice::new(s, s),
+            ChoiceSpec::Full(c) => c.clone(),
+        }
+    }
+}
+
+/// One question, with the same options as the subcommand of the same
+/// name.
+#[derive(Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum Prompt {
+    #[serde(rename_all = "kebab-case")]
+    Ask {
+        question: String,
+        #[serde(default)]
+        default: String,
+        #[serde(default)]
+        allow_blank: bool,
+        #[serde(default)]
+        suggestions: Vec<String>,
+    },
+    Confirm {
+        question: String,
+        default: Option<bool>,
+    },
+    Choose {
+        question: String,
+        options: Vec<ChoiceSpec>,
+        #[serde(default)]
+        default: String,
+    },
+    Select {
+        question: String,
+        options: Vec<ChoiceSpec>,
+        #[serde(default)]
+        default: Vec<String>,
+    },
+    #[serde(rename_all = "kebab-case")]
+    Date {
+        question: String,
+        #[serde(default)]
+        default: String,
+        #[serde(default = "default_date_format")]
+        format: String,
+        #[serde(default)]
+        min_date: String,
+        #[serde(default)]
+        max_date: String,
+        #[serde(default)]
+        starting_date: String,
+        #[serde(default)]
+        week_start: Option<String>,
+        #[serde(default)]
+        help_message: String,
+    },
+    #[serde(rename_all = "kebab-case")]
+    Editor {
+        question: String,
+        #[serde(default)]
+        default: String,
+        #[serde(default)]
+        help_message: String,
+        #[serde(default)]
+        file_extension: String,
+    },
+}
+
+fn default_date_format() -> String {
+    "%Y-%m-%d".to_string()
+}
+
+impl Form {
+    /// Load a form spec, parsed as JSON if the file name ends in
+    /// `.json`, otherwise as TOML.
+    pub fn load(path: &Path) -> Result<Form, FormError> {
+        let text = std::fs::read_to_string(path).map_err(|source| FormError::Read {
+            path: path.display().to_string(),
+            source,
+        })?;
+        let form: Form = match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => serde_json::from_str(&text)?,
+            _ => toml::from_str(&text)?,
+        };
+        // The names become shell variables with --format env:
+        for field in &form.fields {
+            if !is_variable_name(&field.name) {
+                return Err(FormError::Invalid {
+                    name: field.name.clone(),
+                    reason: "the name must be letters, digits and _, not starting with a digit"
+                        .to_string(),
+                });
+            }
+        }
+        Ok(form)
+    }
+
+    /// Ask every field in order, returning the answers keyed by field
+    /// name. Fields whose `when` condition fails are skipped.
+    ///
+    /// Pressing ESC goes back to the previous question, with its
+    /// earlier answer as the default. ESC on the first question cancels
+    /// the whole form.
+    ///
+    /// With `review`, finish on a summary screen where any answer can
+    /// be changed before confirming.
+    pub fn run(&self, review: bool) -> Result<Map<String, Value>, WizardError> {
+        // Every answer given so far, including ones since backed out of:
+        let mut previous: HashMap<&str, Value> = HashMap::new();
+        let answers = self.ask_fields(Map::new(), &mut previous)?;
+        match review {
+            true => self.review(answers, &mut previous),
+            false => Ok(answers),
+        }
+    }
+
+    /// Ask the fields that pass their `when` condition and don't have
+    /// an answer yet, and drop the answers of those that don't pass.
+    fn ask_fields<'a>(
+        &'a self,
+        mut answers: Map<String, Value>,
+        previous: &mut HashMap<&'a str, Value>,
+    ) -> Result<Map<String, Value>, WizardError> {
+        // The indexes of the fields that were asked, to go back through:
+        let mut asked: Vec<usize> = vec![];
+        let mut i = 0;
+        while let Some(field) = self.fields.get(i) {
+            if !field.when.as_ref().is_none_or(|c| c.matches(&answers)) {
+                answers.shift_remove(&field.name);
+                i += 1;
+                continue;
+            }
+            if answers.contains_key(&field.name) {
+                asked.push(i);
+                i += 1;
+                continue;
+            }
+            let answer = answers::with_key(&field.name, || {
+                field.prompt.ask(previous.get(field.name.as_str()))
+            });
+            match answer {
+                Ok(answer) => {
+                    answers.insert(field.name.clone(), answer.clone());
+                    previous.insert(&field.name, answer);
+                    asked.push(i);
+                    i += 1;
+                }
+                Err(WizardError::Canceled) => match asked.pop() {
+                    Some(back) => {
+                        for f in &self.fields[back..] {
+                            answers.shift_remove(&f.name);
+                        }
+                        i = back;
+                    }
+                    None => return Err(WizardError::Canceled),
+                },
+                Err(e) => return Err(e),
+            }
+        }
+        // Keep the answers in the same order as the fields:
+        Ok(self
+            .fields
+            .iter()
+            .filter_map(|f| answers.get(&f.name).map(|a| (f.name.clone(), a.clone())))
+            .collect())
+    }
+
+    /// List every answer and let the user pick one to change, until
+    /// they choose to finish (and confirm) or abort.
+    fn review<'a>(
+        &'a self,
+        mut answers: Map<String, Value>,
+        previous: &mut HashMap<&'a str, Value>,
+    ) -> Result<Map<String, Value>, WizardError> {
+        loop {
+            let fields: Vec<&Field> = self
+                .fields
+                .iter()
+                .filter(|f| answers.contains_key(&f.name))
+                .collect();
+            let mut choices: Vec<ask::Choice> = fields
+                .iter()
+                .map(|f| {
+                    let label = format!("{}: {}", f.name, summarize(&answers[&f.name]));
+                    ask::Choice::new(&label, &f.name)
+                })
+                .collect();
+            choices.push(ask::Choice::new("Done", ""));
+            choices.push(ask::Choice::new("Abort", ""));
+            let done = fields.len();
+            let picked = ask::ChooseBuilder::new("Review your answers, choose one to change it")
+                .choices(choices)
+                .default(&done.to_string())
+                .numeric(true)
+                .prompt()?
+                .parse::<usize>()
+                .unwrap_or(done);
+            if picked == done {
+                let finish = ask::ConfirmBuilder::new("Finish with these answers?")
+                    .default(Some(ask::Confirmation::Yes))
+                    .prompt()?;
+                if finish {
+                    return Ok(answers);
+                }
+                continue;
+            }
+            let Some(field) = fields.get(picked) else {
+                return Err(WizardError::Canceled);
+            };
+            // ESC while changing an answer keeps the old one:
+            let answer =
+                answers::with_key(&field.name, || field.prompt.ask(answers.get(&field.name)));
+            match answer {
+                Ok(answer) => {
+                    let mut changed = answers.clone();
+                    changed.insert(field.name.clone(), answer.clone());
+                    previous.insert(&field.name, answer);
+                    // The change may have switched other fields on or off:
+                    match self.ask_fields(changed, previous) {
+                        Ok(changed) => answers = changed,
+                        Err(WizardError::Canceled) => {}
+                        Err(e) => return Err(e),
+                    }
+                }
+                Err(WizardError::Canceled) => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+/// Show an answer on a single line for the review screen.
+fn summarize(answer: &Value) -> String {
+    match answer {
+        Value::String(s) => match s.lines().count() {
+            0 | 1 => s.clone(),
+            _ => format!("{}...", s.lines().next().unwrap_or_default()),
+        },
+        Value::Bool(true) => "yes".to_string(),
+        Value::Bool(false) => "no".to_string(),
+        Value::Array(items) => items
+            .iter()
+            .map(|i| i.as_str().map(String::from).unwrap_or(i.to_string()))
+            .collect::<Vec<_>>()
+            .join(", "),
+        v => v.to_string(),
+    }
+}
+
+/// The text of an earlier answer, to use as a default.
+fn default_text(previous: Option<&Value>, default: &str) -> String {
+    match previous {
+        Some(Value::String(s)) => s.clone(),
+        _ => default.to_string(),
+    }
+}
+
+impl Prompt {
+    /// Ask the question, using the `previous` answer (if any) as the
+    /// default instead of the one from the spec.
+    pub fn ask(&self, previous: Option<&Value>) -> Result<Value, WizardError> {
+        Ok(match self {
+            Prompt::Ask {
+                question,
+                default,
+                allow_blank,
+                suggestions,
+            } => Value::from(
+                ask::AskBuilder::new(question)
+                    .default(&default_text(previous, default))
+                    .allow_blank(*allow_blank)
+                    .suggestions(suggestions)
+                    .prompt()?,
+            ),
+            Prompt::Confirm { question, default } => {
+                let default = previous.and_then(Value::as_bool).or(*default);
+                let default = default.map(|d| match d {
+                    true => ask::Confirmation::Yes,
+                    false => ask::Confirmation::No,
+                });
+                Value::from(
+                    ask::ConfirmBuilder::new(question)
+                        .default(default)
+                        .prompt()?,
+                )
+            }
+            Prompt::Choose {
+                question,
+                options,
+                default,
+            } => Value::from(
+                ask::ChooseBuilder::new(question)
+                    .choices(options.iter().map(ChoiceSpec::to_choice).collect())
+                    .default(&default_text(previous, default))
+                    .prompt()?,
+            ),
+            Prompt::Select {
+                question,
+                options,
+                default,
+            } => {
+                let defaults = match previous {
+                    Some(Value::Array(items)) => items
+                        .iter()
+                        .filter_map(|i| i.as_str().map(String::from))
+                        .collect(),
+                    _ => default.clone(),
+                };
+                Value::from(
+                    ask::SelectBuilder::new(question)
+                        .choices(options.iter().map(ChoiceSpec::to_choice).collect())
+                        .defaults(defaults)
+                        .prompt()?,
+                )
+            }
+            Prompt::Date {
+                question,
+                default,
+                format,
+                min_date,
+                max_date,
+                starting_date,
+                week_start,
+                help_message,
+            } => Value::from(
+                ask::DateBuilder::new(question)
+                    .default(&default_text(previous, default))
+                    .min_date(min_date)
+                    .max_date(max_date)
+                    .starting_date(starting_date)
+                    .week_start(
+                        week_start
+                            .as_deref()
+                            .and_then(|w| w.parse().ok())
+                            .unwrap_or(chrono::Weekday::Sun),
+                    )
+                    .help_message(help_message)
+                    .format(format)
+                    .prompt()?,
+            ),
+            Prompt::Editor {
+                question,
+                default,
+                help_message,
+                file_extension,
+            } => Value::from(
+                ask::EditorBuilder::new(question)
+                    .default(&default_text(previous, default))
+                    .help_message(help_message)
+                    .file_extension(file_extension)
+                    .prompt()?,
+            ),
+        })
+    }
+}
+
+/// Whether `name` can be a shell variable: `[A-Za-z_][A-Za-z0-9_]*`.
+fn is_variable_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Quote `s` for safe use in a POSIX shell.
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+/// Render the answers as `KEY='value'` lines for `eval` in a shell
+/// script. Lists are joined one item per line.
+pub fn to_env(answers: &Map<String, Value>) -> String {
+    answers
+        .iter()
+        .map(|(key, value)| {
+            let value = match value {
+                Value::String(s) => s.clone(),
+                Value::Array(items) => items
+                    .iter()
+                    .map(|i| i.as_str().map(String::from).unwrap_or(i.to_string()))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+                v => v.to_string(),
+            };
+            format!("{}={}", key, shell_quote(&value))
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Load a spec from a temporary file named `name`.
+    fn load(name: &str, spec: &str) -> Result<Form, FormError> {
+        let path =
+            std::env::temp_dir().join(format!("script-wizard-{}-{}", std::process::id(), name));
+        std::fs::write(&path, spec).unwrap();
+        let form = Form::load(&path);
+        std::fs::remove_file(path).unwrap();
+        form
+    }
+
+    #[test]
+    fn loads_toml_and_json_specs() {
+        let form = load(
+            "form.toml",
+            r#"
+            [[fields]]
+            name = "project_name"
+            kind = "ask"
+            question = "Project name?"
+
+            [[fields]]
+            name = "_License2"
+            kind = "choose"
+            question = "License?"
+            options = ["MIT", { label = "Apache 2.0", value = "apache" }]
+            when = { field = "project_name" }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(form.fields.len(), 2);
+        assert!(
+            matches!(&form.fields[1].prompt, Prompt::Choose { options, .. } if options.len() == 2)
+        );
+
+        let form = load(
+            "form.json",
+            r#"{"fields": [
+                {"name": "ok", "kind": "confirm", "question": "OK?", "default": true}
+            ]}"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            form.fields[0].prompt,
+            Prompt::Confirm {
+                default: Some(true),
+                ..
+            }
+        ));
+
+        assert!(matches!(load("bad.json", "{"), Err(FormError::Json { .. })));
+        let unknown_kind = "[[fields]]\nname = \"x\"\nkind = \"dance\"\nquestion = \"?\"";
+        assert!(matches!(
+            load("bad.toml", unknown_kind),
+            Err(FormError::Toml { .. })
+        ));
+    }
+
+    #[test]
+    fn rejects_names_that_are_not_shell_variables() {
+        for name in [
+            "",
+            "1st",
+            "first-name",
+            "a b",
+            "x;rm -rf ~",
+            "$HOME",
+            "naïve",
+        ] {
+            let spec = json!({"fields": [{"name": name, "kind": "ask", "question": "?"}]});
+            let loaded = load("names.json", &spec.to_string());
+            assert!(
+                matches!(loaded, Err(FormError::Invalid { .. })),
+                "{:?}",
+                name
+            );
+        }
+    }
+
+    fn three_fields() -> Form {
+        toml::from_str(
+            r#"
+            [[fields]]
+            name = "a"
+            kind = "ask"
+            question = "A?"
+
+            [[fields]]
+            name = "b"
+            kind = "ask"
+            question = "B?"
+
+            [[fields]]
+            name = "c"
+            kind = "confirm"
+            question = "C?"
+            "#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn goes_back_a_field_on_esc() {
+        use crate::backend::{with_backend, ScriptedBackend};
+        use std::rc::Rc;
+
+        let form = three_fields();
+        // ESC on C goes back to B, which defaults to its earlier answer:
+        let script = Rc::new(ScriptedBackend::new([
+            json!("one"),
+            json!("two"),
+            json!(null),
+            json!(""),
+            json!(true),
+        ]));
+        let answers = with_backend(script.clone(), || form.run(false)).unwrap();
+        assert_eq!(
+            Value::Object(answers),
+            json!({"a": "one", "b": "two", "c": true})
+        );
+        assert_eq!(script.remaining(), 0);
+
+        let script = Rc::new(ScriptedBackend::new([
+            json!("one"),
+            json!(null),
+            json!(null),
+        ]));
+        let canceled = with_backend(script, || form.run(false));
+        assert!(matches!(canceled, Err(WizardError::Canceled)));
+    }
+
+    #[test]
+    fn changes_one_answer_at_review() {
+        use crate::backend::{with_backend, ScriptedBackend};
+        use std::rc::Rc;
+
+        let form = three_fields();
+        let script = Rc::new(ScriptedBackend::new([
+            json!("one"),
+            json!("two"),
+            json!(false),
+            // Change B, then start changing A but ESC to keep it:
+            json!("b"),
+            json!("deux"),
+            json!("a"),
+            json!(null),
+            json!("Done"),
+            json!("yes"),
+        ]));
+        let answers = with_backend(script.clone(), || form.run(true)).unwrap();
+        assert_eq!(
+            Value::Object(answers),
+            json!({"a": "one", "b": "deux", "c": false})
+        );
+        assert_eq!(script.remaining(), 0);
+    }
+
+    #[test]
+    fn finishes_or_aborts_at_review() {
+        use crate::backend::{with_backend, ScriptedBackend};
+        use std::rc::Rc;
+
+        let form = three_fields();
+        // Not finishing goes back to the review:
+        let script = Rc::new(ScriptedBackend::new([
+            json!("one"),
+            json!("two"),
+            json!(true),
+            json!("Done"),
+            json!("no"),
+            json!("Done"),
+            json!(""),
+        ]));
+        let answers = with_backend(script.clone(), || form.run(true)).unwrap();
+        assert_eq!(
+            Value::Object(answers),
+            json!({"a": "one", "b": "two", "c": true})
+        );
+        assert_eq!(script.remaining(), 0);
+
+        let script = Rc::new(ScriptedBackend::new([
+            json!("one"),
+            json!("two"),
+            json!(true),
+            json!("Abort"),
+        ]));
+        let aborted = with_backend(script, || form.run(true));
+        assert!(matches!(aborted, Err(WizardError::Canceled)));
+    }
+
+    #[test]
+    fn quotes_answers_for_the_shell() {
+        let answers = json!({
+            "name": "it's $HOME `pwd`",
+            "tags": ["a b", "c'd"],
+            "ok": true,
+            "count": 3,
+        });
+        assert_eq!(
+            to_env(answers.as_object().unwrap()),
+            "name='it'\\''s $HOME `pwd`'\ntags='a b\nc'\\''d'\nok='true'\ncount='3'"
+        );
+    }
+}
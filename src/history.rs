@@ -0,0 +1,114 @@
+//! The commands last run from each menu, kept across runs in
+//! `$XDG_STATE_HOME/script-wizard/`.
+
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// How many commands to remember per menu
+const KEEP: usize = 10;
+
+/// The commands last run from a menu, by the labels leading to them
+/// from the top level menu.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub(crate) struct History {
+    /// Where to save the history, if anywhere
+    #[serde(skip)]
+    file: Option<PathBuf>,
+    /// What the history is for, in case two menus share a file name
+    menu: String,
+    /// The most recent first
+    recent: Vec<Vec<String>>,
+}
+
+impl History {
+    /// The history of `menu`, which identifies it, eg. the path of its
+    /// file. It's empty when there is none, or it can't be read.
+    pub(crate) fn load(menu: &str) -> History {
+        match state_dir() {
+            Some(dir) => History::at(dir.join(format!("menu-{:016x}.json", fnv1a(menu))), menu),
+            None => History::default(),
+        }
+    }
+
+    pub(crate) fn at(file: PathBuf, menu: &str) -> History {
+        let saved = std::fs::read_to_string(&file)
+            .ok()
+            .and_then(|text| serde_json::from_str::<History>(&text).ok())
+            .filter(|history| history.menu == menu);
+        History {
+            file: Some(file),
+            menu: menu.to_string(),
+            recent: saved.map(|history| history.recent).unwrap_or_default(),
+        }
+    }
+
+    /// The labels of the commands last run, the most recent first.
+    pub(crate) fn recent(&self) -> &[Vec<String>] {
+        &self.recent
+    }
+
+    /// The label of the entry last used in the (sub)menu at `path`.
+    pub(crate) fn last_in(&self, path: &[String]) -> Option<&str> {
+        self.recent
+            .iter()
+            .find(|labels| labels.len() > path.len() && labels.starts_with(path))
+            .map(|labels| labels[path.len()].as_str())
+    }
+
+    /// Remember the command at `path` as the one last run, and save.
+    pub(crate) fn record(&mut self, path: &[String]) {
+        self.recent.retain(|labels| labels != path);
+        self.recent.insert(0, path.to_vec());
+        self.recent.truncate(KEEP);
+        let Some(file) = &self.file else {
+            return;
+        };
+        // The history is only a convenience, failing to save it
+        // shouldn't stop the menu:
+        if let Some(dir) = file.parent() {
+            let _ = std::fs::create_dir_all(dir);
+        }
+        if let Ok(text) = serde_json::to_string_pretty(self) {
+            let _ = std::fs::write(file, text);
+        }
+    }
+}
+
+/// `$XDG_STATE_HOME/script-wizard`, or `~/.local/state/script-wizard`.
+fn state_dir() -> Option<PathBuf> {
+    let state = match std::env::var_os("XDG_STATE_HOME").map(PathBuf::from) {
+        Some(dir) if dir.is_absolute() => dir,
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".local/state"),
+    };
+    Some(state.join("script-wizard"))
+}
+
+/// A hash that stays the same across builds, to name history files by.
+fn fnv1a(s: &str) -> u64 {
+    s.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remembers_the_last_commands() {
+        let file =
+            std::env::temp_dir().join(format!("script-wizard-history-{}.json", std::process::id()));
+        let path = |labels: &[&str]| labels.iter().map(|l| l.to_string()).collect::<Vec<_>>();
+        let mut history = History::at(file.clone(), "ops.toml");
+        history.record(&path(&["Services", "Restart nginx"]));
+        history.record(&path(&["Disk usage"]));
+        history.record(&path(&["Services", "Restart nginx"]));
+
+        let history = History::at(file.clone(), "ops.toml");
+        assert_eq!(history.recent().len(), 2);
+        assert_eq!(history.last_in(&[]), Some("Services"));
+        assert_eq!(history.last_in(&path(&["Services"])), Some("Restart nginx"));
+        assert!(History::at(file.clone(), "other.toml").recent().is_empty());
+        std::fs::remove_file(file).unwrap();
+    }
+}
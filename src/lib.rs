@@ -33,112 +33,367 @@
 //! ```
 //!
 //! ## Examples in Bash
-//! 
+//!
 //! ### ask
-//! 
+//!
 //! Ask the user a question and capture the response:
-//! 
+//!
 //! ```bash
 //! # Set the alias to make it easier to use:
 //! alias ask='script-wizard ask'
-//! 
+//!
 //! # Record the user's response into the NAME variable:
 //! NAME=$(ask "What is your name?")
 //! ```
-//! 
+//!
 //! ### confirm
-//! 
+//!
 //! Ask the user a yes/no question, with a prepared default response (eg.
 //! `yes` is the default here) :
-//! 
+//!
 //! ```bash
 //! # Set the alias to make it easier to use:
 //! alias confirm='script-wizard confirm'
-//! 
+//!
 //! # Confirm returns an exit code: 0=yes 1=no :
 //! if confirm "Do you like Linux?" yes; then
 //!   echo "Tux is great!"
 //! else
 //!   echo "Well, thats ok."
 //! fi
-//! 
+//!
 //! # But maybe you want to record a literal "true" or "false" into a variable?:
 //! LIKES_LINUX=$(confirm "Do you like Linux?" yes && echo "true" || echo "false")
 //! ```
-//! 
+//!
+//! ### password
+//!
+//! Ask the user for a secret without echoing it to the screen. The input
+//! is masked with `*` by default (`--display hidden|masked|full`), and
+//! `--confirm` asks for it a second time, re-asking if the two entries
+//! don't match:
+//!
+//! ```bash
+//! DB_PASSWORD=$(script-wizard password "Database password" --confirm --min-length 12)
+//! ```
+//!
+//! ### number
+//!
+//! Ask for a number, re-asking until the answer parses and lies within
+//! the optional `--min`/`--max` range. Use `--integer` to only accept
+//! whole numbers, or `--float` to always print a decimal number (eg.
+//! `8080.0`), and `--step` to let the up/down keys add or subtract that
+//! much (a positive number, and a whole one with `--integer`):
+//!
+//! ```bash
+//! PORT=$(script-wizard number "Which port?" 8080 --integer --min 1 --max 65535)
+//! ```
+//!
+//! ### path
+//!
+//! Ask for a file or directory path, completing names from the
+//! filesystem as you type (press tab). A leading `~` is expanded, and
+//! hidden files are suggested once you type a `.`, or press Ctrl-T to
+//! show or hide them (`--hidden` shows them to begin with). Use
+//! `--must-exist`, `--dir-only`, `--file-only` and `--glob` to restrict
+//! the answer, and `--relative-to` to print it relative to another
+//! directory:
+//!
+//! ```bash
+//! CONFIG=$(script-wizard path "Config file" --must-exist --glob '*.toml' --relative-to "${PWD}")
+//! ```
+//!
 //! ### choose
-//! 
+//!
 //! Present a list of options to the user and have them select a *single*
 //! response from the list:
-//! 
+//!
 //! ```bash
 //! # Set the alias to make it easier to use:
 //! alias choose='script-wizard choose'
-//! 
+//!
 //! CHOSEN=$(choose "Select your character class" "Rogue" "Wizard" "Paladin" "Cleric" "Bard")
 
 //! # You can use an option from a bash array too:
 //! options=("red" "blue" "greenish orange" "purple")
 //! COLOR=$(choose "Choose a color" "${options[@]}")
 //! ```
-//! 
+//!
 //! ### select
-//! 
+//!
 //! Present a list of options to the user and have them select *multiple*
 //! responses (zero or more) from the list:
-//! 
+//!
 //! ```bash
 //! readarray -t SELECTED < <(script-wizard select "Which games do you like?" "Rocket League" "Portal" "Quake" "Magic the Gathering")
-//! 
+//!
 //! echo "These are the games you said you like:"
 //! # Use printf to print one per line (echo would merge into one line):
 //! printf '%s\n' "${SELECTED[@]}"
 //! ```
-//! 
+//!
+//! ### Reading choices from stdin or a file
+//!
+//! `choose` and `select` can read their options from stdin (pass `-` as
+//! the only option) or from `--options-file PATH`, one per line. Add
+//! `--null` (`-0`) to split on NUL bytes instead, so entries may contain
+//! newlines. The prompt itself still reads the keyboard from the
+//! terminal:
+//!
+//! ```bash
+//! FILE=$(find . -name '*.conf' -print0 | script-wizard choose "Which config?" - -0)
+//! ```
+//!
+//! ### Labels and values
+//!
+//! To show friendly labels but print a machine value, give each option
+//! as `LABEL=VALUE` along with `--separator =`, or pass a JSON array of
+//! `{"label", "value", "description", "hotkey"}` objects with
+//! `--options-json`. The description is shown below the list while its
+//! option is highlighted, and pressing an option's one character hotkey
+//! picks it (toggles it with `select`). While any option has a hotkey,
+//! type `/` to filter the list:
+//!
+//! ```bash
+//! ENV=$(script-wizard choose "Deploy to" --separator = "Production=prod" "Staging=stg")
+//! ENV=$(script-wizard choose "Deploy to" --options-json \
+//!   '[{"label": "Production", "value": "prod", "description": "the live site"}]')
+//! ```
+//!
 //! ### date
-//! 
+//!
 //! Present a date picker to the user:
-//! 
+//!
 //! ```bash
 //! # Pick a date between 2023/10/01 and 2023/10/20:
 //! DATE=$(script-wizard date "Enter a date" --week-start monday --format "%Y-%m-%d" --min-date "2023-10-01" --max-date "2023-10-20" --help-message "yadda yadda")
 //! ```
-//! 
+//!
 //! ### editor
-//! 
+//!
 //! Present a full text editor entry to the user:
-//! 
+//!
 //! ```bash
 //! BIOGRAPHY=$(script-wizard editor "Tell me alllll about yourself" --default "# Describe yourself" --json | sed 's/^[^\"]*//')
 //! ```
-//! 
+//!
 //! Watch out: There is a potential bug here if your editor prints
 //! anything to stdout. (In the case of emacsclient, it undesirably
 //! captures the text "Waiting for Emacs...".) Using `--json` will wrap
 //! the correct editor text in double quotes, and pipeing the output
 //! through `sed 's/^[^\"]*//'` will remove the text before the first
 //! double quote.)
-//! 
+//!
 //! Set the common `EDITOR` environment variable to choose the editor it
 //! launches.
-//! 
+//!
+//! ### form
+//!
+//! Ask a series of questions defined in a TOML (or JSON, if the file name
+//! ends in `.json`) spec, and print all the answers at once. Each field
+//! has a `name` (letters, digits and `_`, not starting with a digit, so
+//! it can be a shell variable), a `kind` (`ask`, `confirm`, `choose`,
+//! `select`, `date` or `editor`), a `question`, and the same options as
+//! the subcommand of that name:
+//!
+//! ```toml
+//! [[fields]]
+//! name = "HOST"
+//! kind = "ask"
+//! question = "Hostname?"
+//! default = "localhost"
+//!
+//! [[fields]]
+//! name = "ENV"
+//! kind = "choose"
+//! question = "Deploy to"
+//! options = ["dev", { label = "Production", value = "prod" }]
+//! ```
+//!
+//! A field can be made conditional on an earlier answer with `when`. The
+//! answer must be truthy, or match `equals`, `not-equals` or `one-of`:
+//!
+//! ```toml
+//! [[fields]]
+//! name = "CERT"
+//! kind = "ask"
+//! question = "Certificate path?"
+//! when = { field = "ENV", equals = "prod" }
+//! ```
+//!
+//! Fields that are skipped are left out of the answers. Press ESC to go
+//! back to the previous question (with your earlier answer as the
+//! default); ESC on the first question cancels the form.
+//!
+//! With `--review`, the form ends on a summary of every answer. Choose
+//! one to answer it again, or choose `Done` to confirm and finish.
+//!
+//! The answers are printed as one JSON object keyed by field name, or as
+//! `KEY='value'` lines with `--output env`:
+//!
+//! ```bash
+//! eval "$(script-wizard form setup.toml --output env)"
+//! ```
+//!
 //! ### menu
-//! 
+//!
 //! Present a menu of command entries that the user can select and
 //! execute. The entries must be specified in the format: `ENTRY =
 //! COMMAND` where `ENTRY` is the text line of the menu entry, and
 //! `COMMAND` is the shell command to run if the entry is selected:
-//! 
+//!
 //! ```bash
 //! script-wizard menu --once "main menu" "print username = whoami"  "print all users = cat /etc/passwd | cut -d ':' -f 1"
 //! ```
 //!
+//! For anything bigger, define the menu in a TOML (or JSON, if the file
+//! name ends in `.json`) file and give it with `--file`. Each entry has a
+//! `label` and an optional `description`, and does one of three things:
+//! runs a `command` (a shell command line, or a list of a program and its
+//! arguments to run without a shell), opens a submenu of more `entries`,
+//! or, with `back = true`, returns to the parent menu. `cwd` and `env`
+//! set the working directory (relative to the menu file) and environment
+//! variables of an entry's command, or of every command in its submenu:
+//!
+//! ```toml
+//! heading = "Ops console"
+//!
+//! [[entries]]
+//! label = "Disk usage"
+//! command = "df -h"
+//!
+//! [[entries]]
+//! label = "Services"
+//! description = "restart and inspect services"
+//! env = { SYSTEMD_COLORS = "0" }
+//!
+//!   [[entries.entries]]
+//!   label = "Restart nginx"
+//!   command = ["sudo", "systemctl", "restart", "nginx"]
+//!
+//!   [[entries.entries]]
+//!   label = "Back"
+//!   back = true
+//! ```
+//!
+//! ```bash
+//! script-wizard menu --file ops.toml
+//! ```
+//!
+//! ESC in a submenu also goes back to its parent. A heading given on the
+//! command line replaces the file's `heading`.
+//!
+//! Entries can be guarded, to keep destructive commands from being run
+//! by accident:
+//!
+//!  * `confirm = "Really drop the database?"` asks for a yes before the
+//!    entry is run; no (the default) returns to the menu.
+//!  * `key = "d"` shows `[d]` next to the label, and pressing `d` picks
+//!    the entry. Type `/` first to filter the menu instead.
+//!  * `separator = true` adds a line between groups of entries, with its
+//!    `label`, if any, as their heading. It can't be chosen.
+//!  * `guard` is a command (a line or a list, like `command`) that has to
+//!    succeed for the entry to be available. It is run quietly in the
+//!    entry's `cwd` and `env` each time the menu is shown. Until it
+//!    succeeds the entry is shown as unavailable and can't be chosen, or,
+//!    with `unavailable = "hide"`, left out of the menu.
+//!
+//! ```toml
+//! [[entries]]
+//! separator = true
+//! label = "Database"
+//!
+//! [[entries]]
+//! label = "Drop database"
+//! key = "d"
+//! confirm = "Really drop the database?"
+//! guard = "test -f .env"
+//! unavailable = "hide"
+//! command = "./manage.sh drop"
+//! ```
+//!
+//! Commands are run with `bash -c` unless `--shell` (or `shell` at the
+//! top of a menu file) picks `sh`, `zsh`, `fish`, or `none` to split the
+//! line into words, honouring quotes and backslashes, and run the program
+//! directly. A command that exits with 0 returns to the menu (or, with
+//! `--once`, leaves it), and one that exits with 2 quits the menu, with
+//! exit code 2. What happens on any other exit code is up to
+//! `--on-failure` (or `on_failure`):
+//!
+//!  * `abort`, the default, leaves the menu and exits with the command's
+//!    exit code.
+//!  * `stay` shows `command failed (exit N)` and offers to retry the
+//!    command, before returning to the menu.
+//!
+//! ```bash
+//! script-wizard menu --shell none --on-failure stay "Deploy" \
+//!   "Staging = ./deploy.sh staging" "Production = ./deploy.sh 'prod eu'"
+//! ```
+//!
+//! To read long output before the menu is drawn again, give `--pager`
+//! (or `pager = true` at the top of a menu file, or on an entry or a
+//! submenu). The command's output, stdout and stderr together, is then
+//! captured and shown in a pager, with the exit status in its footer.
+//! Scroll with the arrow keys, `j`/`k`, space/`b` and `g`/`G`, search with
+//! `/` and `n`/`N`, and press `q` to return to the menu. A paged command
+//! gets no input. An entry can opt out with `pager = false`.
+//!
+//! Menus remember the commands run from them, in a file per menu under
+//! `$XDG_STATE_HOME/script-wizard/` (`~/.local/state/script-wizard/` if
+//! that isn't set). The next time the menu is shown, the last few of
+//! them are listed in a "Recent" section at the top, the cursor starts
+//! on the most recent one, and pressing `r` runs it again (unless an
+//! entry has `r` as its key). Like with entry keys, type `/` first to
+//! filter the menu. In a submenu, the cursor starts on the entry last
+//! used there. Give `--no-history` to neither remember nor offer them.
+//!
+//! ## Using from Rust
+//!
+//! The same prompts are available as a library, as builders in the `ask`
+//! module. Each `prompt()` returns a `Result`, with a `WizardError` that
+//! tells apart the user canceling (`Canceled`), pressing Ctrl-C
+//! (`Interrupted`), there being no terminal (`NotTty`) and so on:
+//!
+//! ```rust,no_run
+//! use script_wizard::ask::{ChooseBuilder, ConfirmBuilder, WizardError};
+//!
+//! fn main() -> Result<(), WizardError> {
+//!     let class = ChooseBuilder::new("Select your character class")
+//!         .options(["Rogue", "Wizard", "Paladin"])
+//!         .default("Wizard")
+//!         .prompt()?;
+//!     match ConfirmBuilder::new(&format!("Play as a {}?", class)).prompt() {
+//!         Ok(true) => println!("Let's go!"),
+//!         Ok(false) | Err(WizardError::Canceled) => println!("Maybe later."),
+//!         Err(e) => return Err(e),
+//!     }
+//!     Ok(())
+//! }
+//! ```
+//!
+//! Every prompt is asked through a `PromptBackend` (in the `backend`
+//! module), which is the terminal by default. To test code that asks
+//! questions, run it under `with_backend` with a `ScriptedBackend`,
+//! which gives the answers from a queue instead:
+//!
+//! ```rust
+//! use script_wizard::ask::ConfirmBuilder;
+//! use script_wizard::backend::{with_backend, ScriptedBackend};
+//! use std::rc::Rc;
+//!
+//! let script = Rc::new(ScriptedBackend::new([serde_json::json!("yes")]));
+//! let sure = with_backend(script, || ConfirmBuilder::new("Are you sure?").prompt());
+//! assert!(sure.unwrap());
+//! ```
+//!
 //! ## Babashka pod
 //!
 //! script-wizard can run as a [Babashka](https://github.com/babashka/babashka)
 //! [pod](https://github.com/babashka/pods), providing a native Clojure API
-//! for all commands (`ask`, `confirm`, `choose`, `select`, `date`, `editor`,
-//! and `menu`):
+//! for every subcommand. Each takes the same arguments as on the command
+//! line, with options given as keywords (see `(doc sw/choose)` for the
+//! list). The global options, like `:timeout`, `:key` and `:answers`,
+//! apply to just the call they're given to:
 //!
 //! ```clojure
 //! (require '[babashka.pods :as pods])
@@ -148,13 +403,113 @@
 //! (sw/ask "What is your name?" :default "World")
 //! (sw/confirm "Continue?" :default :yes)
 //! (sw/choose "Pick one" ["a" "b" "c"])
+//! (sw/choose "Pick one" ["a" "b" "c"] :numeric true :cancel-code 2)
+//! (sw/ask "Region?" :default "us-east" :timeout 10)
+//! (sw/menu "Main menu" ["Update = sudo apt update" "Quit = exit 2"])
+//! ```
+//!
+//! When the user cancels a prompt with ESC or Ctrl-C, it throws an
+//! `ex-info` with `{:type :script-wizard/canceled, :prompt "ask", :code 1}`
+//! as its data, where `:code` is the exit code the command line would use.
+//! Give `:on-cancel` to return a value instead:
+//!
+//! ```clojure
+//! (try
+//!   (sw/confirm "Deploy now?")
+//!   (catch clojure.lang.ExceptionInfo e
+//!     (if (= :script-wizard/canceled (:type (ex-data e)))
+//!       (println "Maybe later")
+//!       (throw e))))
+//!
+//! (sw/ask "What is your name?" :on-cancel "Anonymous")
+//! ```
+//!
+//! Canceling a `menu` leaves it and returns nil, unless `:on-cancel`
+//! says otherwise. The `menu` entries may also be `[label handler]` pairs
+//! of Clojure functions instead of shell commands.
+//!
+//! Arguments and results are passed as JSON by default. Give `--format
+//! edn` or `--format transit+json` (or set `SCRIPT_WIZARD_POD_FORMAT`) to
+//! use EDN or transit instead: map keys in results and errors then come
+//! back as keywords rather than strings:
+//!
+//! ```clojure
+//! (pods/load-pod ["script-wizard" "pod" "--format" "edn"])
+//! ```
+//!
+//! ## Unattended runs
+//!
+//! Give `--answers FILE` (or set `SCRIPT_WIZARD_ANSWERS=FILE`) to answer
+//! prompts from a JSON or TOML file instead of asking. The file maps the
+//! question text (or a form field name) to its answer, and each answer is
+//! checked the same way an interactive one would be, eg. a `choose`
+//! answer must be one of the options. Questions without an answer in the
+//! file are still asked interactively, and so is everything a `menu`
+//! asks:
+//!
+//! ```bash
+//! echo '{"What is your name?": "Tux", "Do you like Linux?": "yes"}' > answers.json
+//! SCRIPT_WIZARD_ANSWERS=answers.json ./setup.sh
+//! ```
+//!
+//! To make such a file, run the script once by hand with `--record
+//! FILE` (or `SCRIPT_WIZARD_RECORD=FILE`). Every answer is appended to
+//! it as a line of JSON, along with the question and its options, and
+//! `--answers` accepts the resulting `.jsonl` file as is. Password
+//! answers are redacted (and so still asked for on replay) unless
+//! `--record-secrets` is given:
+//!
+//! ```bash
+//! SCRIPT_WIZARD_RECORD=setup.jsonl ./setup.sh
+//! SCRIPT_WIZARD_ANSWERS=setup.jsonl ./setup.sh
+//! ```
+//!
+//! Any prompt can also be given a name with `--key NAME`. Then
+//! `SCRIPT_WIZARD_DEFAULT_<NAME>` replaces its default answer, and
+//! `SCRIPT_WIZARD_ANSWER_<NAME>` answers it without asking (checked the
+//! same way as typed input). Form fields use their field name as the key.
+//! The name is upper-cased, with anything other than letters and digits
+//! turned into `_`:
+//!
+//! ```bash
+//! PORT=$(script-wizard number "Which port?" 8080 --integer --key port)
+//! # Now in a Makefile: SCRIPT_WIZARD_ANSWER_PORT=9090 ./setup.sh
 //! ```
 //!
 //! ## Common options
-//! 
+//!
 //!  * `--json` - the default is to print raw text even if it spans
 //!    multiple lines. If you specify `--json` it will print it as compact
 //!    JSON on a single line, splitting lines into lists of strings, or as
 //!    a quoted string if its just supposed to be one line, depending on
 //!    the subcommand.
+//!  * `--timeout SECONDS` - count down before showing the prompt (or in
+//!    its help line, for `choose` and `select`). If no key is pressed in
+//!    time, the default answer is used, or if there is no default,
+//!    script-wizard exits with code `124`. Pressing a key stops the
+//!    countdown and goes on to the prompt: enter takes the default, ESC
+//!    cancels and anything else starts the answer. A `menu` ignores it.
+//!  * `--cancel-code CODE` - the exit code when the user presses ESC
+//!    (default `1`).
+//!  * `--interrupt-code CODE` - the exit code when the user presses
+//!    Ctrl-C, eg. `130` (by default, the cancel code).
+//!  * `--error-code CODE` - the exit code when the prompt can't be
+//!    shown or answered at all, eg. there is no TTY, or an answers file
+//!    gives an invalid answer (by default, the cancel code). With
+//!    `--json`, errors are also described on stderr as a JSON object, eg.
+//!    `{"error":"not-tty","message":"...","code":3}` with `--error-code 3`.
+pub mod answers;
 pub mod ask;
+pub mod backend;
+pub mod cli;
+mod example;
+mod form;
+mod history;
+pub mod menu;
+mod pager;
+mod pathinput;
+mod payload;
+mod picker;
+mod pod;
+mod rawprompt;
+mod stepper;
@@ -0,0 +1,1044 @@
+use crate::ask::{Choice, ChooseBuilder, ConfirmBuilder, Confirmation, MenuExit, WizardError};
+use crate::backend::{self, PagerRequest};
+use crate::history::History;
+use clap::ValueEnum;
+use custom_error::custom_error;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Stdio};
+
+custom_error! {pub MenuError
+    Read{path:String, source:std::io::Error} = "Could not read menu file {path}: {source}",
+    Toml{source:toml::de::Error} = "Invalid menu file: {source}",
+    Json{source:serde_json::Error} = "Invalid menu file: {source}",
+    Invalid{label:String, reason:String} = "Invalid menu entry \"{label}\": {reason}",
+}
+
+/// A menu definition, as read from a menu file.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Menu {
+    /// The heading shown above the entries
+    #[serde(default)]
+    pub heading: String,
+    pub entries: Vec<Entry>,
+    /// How to run command lines, bash unless given
+    #[serde(default)]
+    pub shell: Option<Shell>,
+    /// What to do when a command fails, abort unless given
+    #[serde(default)]
+    pub on_failure: Option<OnFailure>,
+    /// Show the output of every command in a pager, unless the entry
+    /// says otherwise
+    #[serde(default)]
+    pub pager: bool,
+    /// The file the menu was loaded from
+    #[serde(skip)]
+    pub(crate) source: Option<PathBuf>,
+}
+
+/// How to run the command lines of a menu.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Shell {
+    #[default]
+    Bash,
+    Sh,
+    Zsh,
+    Fish,
+    /// Split the line into words, and run the program they name
+    /// without any shell
+    None,
+}
+
+impl Shell {
+    /// The shell to run command lines with `-c`, or none to run them
+    /// directly.
+    fn program(self) -> Option<&'static str> {
+        // bash and sh are always in /bin, the others are found in the
+        // PATH:
+        match self {
+            Shell::Bash => Some("/bin/bash"),
+            Shell::Sh => Some("/bin/sh"),
+            Shell::Zsh => Some("zsh"),
+            Shell::Fish => Some("fish"),
+            Shell::None => None,
+        }
+    }
+}
+
+/// Split a command line into words the way a shell would, minus any
+/// expansions: whitespace separates words, unless quoted or escaped
+/// with a backslash. Inside double quotes, a backslash only escapes
+/// `"`, `\`, `$` and `` ` ``.
+fn split_words(line: &str) -> Result<Vec<String>, WizardError> {
+    let invalid = |reason: &str| WizardError::InvalidConfiguration {
+        message: format!("can't run {:?} without a shell: {}", line, reason),
+    };
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => words.extend(word.take()),
+            '\\' => {
+                let escaped = chars
+                    .next()
+                    .ok_or_else(|| invalid("it ends in a backslash"))?;
+                word.get_or_insert_with(String::new).push(escaped);
+            }
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err(invalid("a ' quote is not closed")),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\' | '$' | '`')) => word.push(c),
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => return Err(invalid("a \" quote is not closed")),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err(invalid("a \" quote is not closed")),
+                    }
+                }
+            }
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    if words.is_empty() {
+        return Err(invalid("it is empty"));
+    }
+    Ok(words)
+}
+
+/// What to do when a menu command exits with a code other than 0 or 2.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum OnFailure {
+    /// Leave the menu, exiting with the command's exit code
+    #[default]
+    Abort,
+    /// Show the exit code and offer to retry, then return to the menu
+    Stay,
+}
+
+/// The command of a menu entry.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum EntryCommand {
+    /// A command line, run by bash
+    Shell(String),
+    /// A program and its arguments, run directly
+    Argv(Vec<String>),
+}
+
+/// What to show of an entry while its guard fails.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Unavailable {
+    /// Show it marked as unavailable, and refuse to run it
+    #[default]
+    Disable,
+    /// Leave it out of the menu
+    Hide,
+}
+
+/// An entry of a menu: a command to run, a submenu of more entries, a
+/// way back to the parent menu, or a separator line.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Entry {
+    #[serde(default)]
+    pub label: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub command: Option<EntryCommand>,
+    /// The directory to run the command in. For a submenu, the
+    /// directory for all the commands in it.
+    #[serde(default)]
+    pub cwd: Option<PathBuf>,
+    /// Environment variables to set for the command, or for all the
+    /// commands of a submenu.
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// The entries of the submenu this entry opens
+    #[serde(default)]
+    pub entries: Vec<Entry>,
+    /// Return to the parent menu, or leave the top level menu
+    #[serde(default)]
+    pub back: bool,
+    /// A line between groups of entries, with the label (if any) as
+    /// their heading. It can't be chosen.
+    #[serde(default)]
+    pub separator: bool,
+    /// A key shown next to the label, which picks the entry with a
+    /// single keypress
+    #[serde(default)]
+    pub key: Option<char>,
+    /// A question that has to be answered yes before the entry is run
+    #[serde(default)]
+    pub confirm: Option<String>,
+    /// A command that has to succeed for the entry to be available.
+    /// It is run quietly each time the menu is shown.
+    #[serde(default)]
+    pub guard: Option<EntryCommand>,
+    /// What to do with the entry while its guard fails
+    #[serde(default)]
+    pub unavailable: Unavailable,
+    /// Show the output of the command, or of the commands of a
+    /// submenu, in a pager instead of on the terminal
+    #[serde(default)]
+    pub pager: Option<bool>,
+}
+
+impl Entry {
+    fn choice(&self, value: usize, available: bool) -> Choice {
+        let label = match (self.separator, self.label.as_str(), available) {
+            (true, "", _) => "────────".to_string(),
+            (true, heading, _) => format!("── {} ──", heading),
+            (false, label, true) => label.to_string(),
+            (false, label, false) => format!("{} (unavailable)", label),
+        };
+        let mut choice = Choice::new(&label, &value.to_string());
+        choice.description = self.description.clone();
+        choice.hotkey = self.key;
+        choice.disabled = self.separator || !available;
+        choice
+    }
+}
+
+/// Where a menu left off.
+enum Step {
+    /// Back to the parent menu
+    Back,
+    /// Leave the menu altogether
+    Exit(MenuExit),
+}
+
+/// The directory and environment for commands, inherited from the
+/// submenus they are in.
+#[derive(Clone, Default)]
+struct Scope {
+    cwd: Option<PathBuf>,
+    env: BTreeMap<String, String>,
+    /// The labels of the entries leading here from the top level menu
+    path: Vec<String>,
+    shell: Shell,
+    on_failure: OnFailure,
+    pager: bool,
+}
+
+impl Scope {
+    fn enter(&self, entry: &Entry) -> Scope {
+        let mut env = self.env.clone();
+        env.extend(entry.env.clone());
+        let mut path = self.path.clone();
+        path.push(entry.label.clone());
+        Scope {
+            cwd: entry.cwd.clone().or(self.cwd.clone()),
+            env,
+            path,
+            pager: entry.pager.unwrap_or(self.pager),
+            ..self.clone()
+        }
+    }
+
+    fn command(&self, command: &EntryCommand) -> Result<Command, WizardError> {
+        let mut cmd = match command {
+            EntryCommand::Shell(line) => match self.shell.program() {
+                Some(shell) => {
+                    let mut cmd = Command::new(shell);
+                    cmd.args(["-c", line]);
+                    cmd
+                }
+                None => {
+                    let argv = split_words(line)?;
+                    let mut cmd = Command::new(&argv[0]);
+                    cmd.args(&argv[1..]);
+                    cmd
+                }
+            },
+            EntryCommand::Argv(argv) => {
+                let mut cmd = Command::new(&argv[0]);
+                cmd.args(&argv[1..]);
+                cmd
+            }
+        };
+        if let Some(cwd) = &self.cwd {
+            cmd.current_dir(cwd);
+        }
+        cmd.envs(&self.env);
+        Ok(cmd)
+    }
+
+    /// Run a command with its output captured, stdout and stderr
+    /// together, and show it in a pager along with how it exited.
+    fn paged(&self, title: &str, command: &EntryCommand) -> Result<ExitStatus, WizardError> {
+        let (mut reader, writer) = std::io::pipe()?;
+        let mut cmd = self.command(command)?;
+        cmd.stdin(Stdio::null())
+            .stdout(writer.try_clone()?)
+            .stderr(writer);
+        eprintln!("Running {}...", title);
+        let mut child = cmd.spawn()?;
+        // Close our end of the pipe, so reading stops with the command:
+        drop(cmd);
+        let mut output = Vec::new();
+        reader.read_to_end(&mut output)?;
+        let status = child.wait()?;
+        let shown = match status.code() {
+            Some(code) => format!("exit {}", code),
+            None => status.to_string(),
+        };
+        backend::current().pager(PagerRequest {
+            title,
+            text: &String::from_utf8_lossy(&output),
+            status: &shown,
+        })?;
+        Ok(status)
+    }
+
+    /// Run a guard command without any input or output, and tell if it
+    /// succeeded.
+    fn succeeds(&self, command: &EntryCommand) -> bool {
+        let Ok(mut cmd) = self.command(command) else {
+            return false;
+        };
+        cmd.stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success())
+    }
+}
+
+impl Menu {
+    /// Load a menu file, parsed as JSON if the file name ends in
+    /// `.json`, otherwise as TOML. Relative `cwd`s are taken from the
+    /// directory the file is in.
+    pub fn load(path: &Path) -> Result<Menu, MenuError> {
+        let text = std::fs::read_to_string(path).map_err(|source| MenuError::Read {
+            path: path.display().to_string(),
+            source,
+        })?;
+        let mut menu: Menu = match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => serde_json::from_str(&text)?,
+            _ => toml::from_str(&text)?,
+        };
+        let dir = path.parent().unwrap_or(Path::new(""));
+        check(&menu.heading, &mut menu.entries, dir)?;
+        menu.source = Some(path.canonicalize().unwrap_or(path.to_path_buf()));
+        Ok(menu)
+    }
+
+    /// Parse entries in the form `ENTRY = COMMAND`.
+    pub fn parse_entries<S: AsRef<str>>(
+        heading: &str,
+        entries: impl IntoIterator<Item = S>,
+    ) -> Result<Menu, WizardError> {
+        let entries = entries
+            .into_iter()
+            .map(|entry| {
+                let entry = entry.as_ref();
+                let (label, command) =
+                    entry
+                        .split_once(" = ")
+                        .ok_or_else(|| WizardError::InvalidConfiguration {
+                            message: format!(
+                                "menu entry {:?} is not in the form ENTRY = COMMAND",
+                                entry
+                            ),
+                        })?;
+                Ok(Entry {
+                    label: label.to_string(),
+                    command: Some(EntryCommand::Shell(command.to_string())),
+                    ..Default::default()
+                })
+            })
+            .collect::<Result<_, WizardError>>()?;
+        Ok(Menu {
+            heading: heading.to_string(),
+            entries,
+            ..Default::default()
+        })
+    }
+
+    /// What identifies the menu in its [`History`]: the path of its
+    /// file, or else its heading and labels.
+    pub(crate) fn history_key(&self) -> String {
+        match &self.source {
+            Some(path) => path.display().to_string(),
+            None => std::iter::once(self.heading.as_str())
+                .chain(self.entries.iter().map(|e| e.label.as_str()))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        }
+    }
+
+    /// Ask the menu in a loop, running the chosen commands, until a
+    /// command exits with code 2 or fails. ESC in a submenu goes back
+    /// to its parent. Commands are recorded in the `history`, which
+    /// also decides the entries to start on unless a `default` is
+    /// given.
+    pub(crate) fn run(
+        &self,
+        default: &str,
+        once: bool,
+        history: &mut History,
+    ) -> Result<MenuExit, WizardError> {
+        let scope = Scope {
+            shell: self.shell.unwrap_or_default(),
+            on_failure: self.on_failure.unwrap_or_default(),
+            pager: self.pager,
+            ..Default::default()
+        };
+        let menu = Level {
+            heading: &self.heading,
+            entries: &self.entries,
+            top: &self.entries,
+            scope: &scope,
+            once,
+        };
+        match menu.run(default, history)? {
+            Step::Back => Ok(MenuExit::Quit),
+            Step::Exit(exit) => Ok(exit),
+        }
+    }
+}
+
+/// Check that every entry does exactly one thing, that no two entries
+/// of a menu share a key, and resolve relative `cwd`s against `dir`.
+fn check(heading: &str, entries: &mut [Entry], dir: &Path) -> Result<(), MenuError> {
+    if entries.iter().all(|entry| entry.separator) {
+        return Err(MenuError::Invalid {
+            label: heading.to_string(),
+            reason: "a menu needs at least one entry".to_string(),
+        });
+    }
+    let mut keys = Vec::new();
+    for entry in entries {
+        let invalid = |reason: &str| MenuError::Invalid {
+            label: entry.label.clone(),
+            reason: reason.to_string(),
+        };
+        let does = [
+            entry.command.is_some(),
+            !entry.entries.is_empty(),
+            entry.back,
+            entry.separator,
+        ];
+        match does.iter().filter(|d| **d).count() {
+            0 => {
+                return Err(invalid(
+                    "it needs a command, entries, back = true or separator = true",
+                ))
+            }
+            1 => {}
+            _ => {
+                return Err(invalid(
+                    "it can only have one of command, entries, back = true or separator = true",
+                ))
+            }
+        }
+        for command in [&entry.command, &entry.guard] {
+            if let Some(EntryCommand::Argv(argv)) = command {
+                if argv.is_empty() {
+                    return Err(invalid("the command list is empty"));
+                }
+            }
+        }
+        if entry.separator {
+            if entry.key.is_some() || entry.confirm.is_some() || entry.guard.is_some() {
+                return Err(invalid("a separator can't have a key, confirm or guard"));
+            }
+        } else if entry.label.is_empty() {
+            return Err(invalid("it needs a label"));
+        }
+        if let Some(key) = entry.key {
+            if keys.contains(&key) {
+                return Err(invalid(&format!(
+                    "the key {:?} is already used in this menu",
+                    key
+                )));
+            }
+            keys.push(key);
+        }
+        if let Some(cwd) = &entry.cwd {
+            entry.cwd = Some(dir.join(cwd));
+        }
+        if !entry.entries.is_empty() {
+            check(&entry.label, &mut entry.entries, dir)?;
+        }
+    }
+    Ok(())
+}
+
+/// Something shown in a menu.
+enum Item<'a> {
+    /// One of the entries of the menu, by its index, and whether its
+    /// guard lets it be run
+    Entry(usize, bool),
+    /// A command run before, found by its path from the top level
+    Recent(&'a Entry, Scope),
+    /// A line around the recent commands
+    Rule(&'static str),
+}
+
+/// A menu or submenu being asked.
+struct Level<'a> {
+    heading: &'a str,
+    entries: &'a [Entry],
+    /// The entries of the top level menu, where recent commands are
+    /// found
+    top: &'a [Entry],
+    scope: &'a Scope,
+    once: bool,
+}
+
+impl<'a> Level<'a> {
+    fn run(&self, default: &str, history: &mut History) -> Result<Step, WizardError> {
+        let nested = !self.scope.path.is_empty();
+        // The entry to start on: the one given, or else the one last
+        // run (at the top level, that's the first recent command), or
+        // else the first one that can be chosen:
+        let mut current = match default.trim().parse::<usize>() {
+            Ok(index) => Some(index),
+            Err(_) if !nested && !history.recent().is_empty() => None,
+            Err(_) => history
+                .last_in(&self.scope.path)
+                .and_then(|label| self.entries.iter().position(|e| e.label == label))
+                .or_else(|| self.entries.iter().position(|e| !e.separator)),
+        };
+        loop {
+            // Check the guards again each time, the last command may
+            // have changed what they test:
+            let items = self.items(history);
+            if !items.iter().any(|item| match item {
+                Item::Entry(i, available) => *available && !self.entries[*i].separator,
+                Item::Recent(..) => true,
+                Item::Rule(_) => false,
+            }) {
+                return Err(WizardError::InvalidConfiguration {
+                    message: format!(
+                        "none of the entries of menu {:?} are available",
+                        self.heading
+                    ),
+                });
+            }
+            let start = items
+                .iter()
+                .position(|item| match (item, current) {
+                    (Item::Entry(i, _), Some(index)) => *i >= index,
+                    (Item::Recent(..), None) => true,
+                    _ => false,
+                })
+                .unwrap_or(0);
+            let mut has_repeat = false;
+            let choices = items
+                .iter()
+                .enumerate()
+                .map(|(position, item)| match item {
+                    Item::Entry(i, available) => self.entries[*i].choice(position, *available),
+                    Item::Recent(_, scope) => {
+                        let mut choice =
+                            Choice::new(&scope.path.join(" › "), &position.to_string());
+                        // The most recent command can be repeated with
+                        // r, unless an entry uses that key:
+                        if !has_repeat && self.entries.iter().all(|e| e.key != Some('r')) {
+                            choice.hotkey = Some('r');
+                        }
+                        has_repeat = true;
+                        choice
+                    }
+                    Item::Rule(heading) => Entry {
+                        label: heading.to_string(),
+                        separator: true,
+                        ..Default::default()
+                    }
+                    .choice(position, true),
+                })
+                .collect();
+
+            eprintln!();
+            let chosen = ChooseBuilder::new(self.heading)
+                .always_ask()
+                .choices(choices)
+                .default(&start.to_string())
+                .numeric(true)
+                .prompt();
+            let item = match chosen {
+                Ok(position) => &items[position.parse::<usize>().unwrap_or(0)],
+                Err(WizardError::Canceled) if nested => return Ok(Step::Back),
+                Err(e) => return Err(e),
+            };
+
+            let index = match item {
+                Item::Entry(index, _) => *index,
+                Item::Recent(entry, scope) => {
+                    current = None;
+                    match self.run_command(entry, scope, history)? {
+                        Some(step) => return Ok(step),
+                        None => continue,
+                    }
+                }
+                Item::Rule(_) => unreachable!("headings can't be chosen"),
+            };
+            current = Some(index);
+            let entry = &self.entries[index];
+            if entry.command.is_none() && !confirmed(entry)? {
+                continue;
+            }
+            if entry.back {
+                return Ok(Step::Back);
+            }
+            let inner = self.scope.enter(entry);
+            if !entry.entries.is_empty() {
+                let submenu = Level {
+                    heading: &entry.label,
+                    entries: &entry.entries,
+                    scope: &inner,
+                    ..*self
+                };
+                match submenu.run("", history)? {
+                    Step::Back => continue,
+                    exit => return Ok(exit),
+                }
+            }
+            if let Some(step) = self.run_command(entry, &inner, history)? {
+                return Ok(step);
+            }
+        }
+    }
+
+    /// The entries of the menu, after the recent commands if it's the
+    /// top level menu, and leaving out those hidden by their guards.
+    fn items(&self, history: &History) -> Vec<Item<'a>> {
+        let mut items = Vec::new();
+        if self.scope.path.is_empty() {
+            items.extend(
+                history
+                    .recent()
+                    .iter()
+                    .filter_map(|path| find(self.top, path, self.scope))
+                    .take(RECENT)
+                    .map(|(entry, scope)| Item::Recent(entry, scope)),
+            );
+            if !items.is_empty() {
+                items.insert(0, Item::Rule("Recent"));
+                items.push(Item::Rule(""));
+            }
+        }
+        for (i, entry) in self.entries.iter().enumerate() {
+            let available = match &entry.guard {
+                Some(guard) => self.scope.enter(entry).succeeds(guard),
+                None => true,
+            };
+            if available || entry.unavailable == Unavailable::Disable {
+                items.push(Item::Entry(i, available));
+            }
+        }
+        items
+    }
+
+    /// Run the command of `entry`, once confirmed, and record it in the
+    /// history. Returns where to go next, unless it's back to the menu.
+    fn run_command(
+        &self,
+        entry: &Entry,
+        scope: &Scope,
+        history: &mut History,
+    ) -> Result<Option<Step>, WizardError> {
+        let Some(command) = &entry.command else {
+            return Ok(None);
+        };
+        if !confirmed(entry)? {
+            return Ok(None);
+        }
+        history.record(&scope.path);
+
+        // Run the command, and again for as long as it fails and the
+        // user asks to retry:
+        loop {
+            let status = match scope.pager {
+                true => scope.paged(&entry.label, command)?,
+                false => scope.command(command)?.status()?,
+            };
+            match status.code().unwrap_or(1) {
+                0 => {
+                    //Keep looping unless --once is given:
+                    if self.once {
+                        return Ok(Some(Step::Exit(MenuExit::Done)));
+                    }
+                }
+                2 => {
+                    // Exit code 2 signals to quit the loop:
+                    return Ok(Some(Step::Exit(MenuExit::Quit)));
+                }
+                code if scope.on_failure == OnFailure::Abort => {
+                    return Err(WizardError::CommandFailed { code });
+                }
+                code => {
+                    eprintln!("command failed (exit {})", code);
+                    match ConfirmBuilder::new("Retry?")
+                        .always_ask()
+                        .default(Some(Confirmation::No))
+                        .prompt()
+                    {
+                        Ok(true) => continue,
+                        Ok(false) | Err(WizardError::Canceled) => {}
+                        Err(e) => return Err(e),
+                    }
+                }
+            }
+            return Ok(None);
+        }
+    }
+}
+
+/// How many recent commands to show
+const RECENT: usize = 3;
+
+/// Ask the `confirm` question of an entry, if it has one. ESC counts
+/// as no.
+fn confirmed(entry: &Entry) -> Result<bool, WizardError> {
+    let Some(question) = &entry.confirm else {
+        return Ok(true);
+    };
+    match ConfirmBuilder::new(question)
+        .always_ask()
+        .default(Some(Confirmation::No))
+        .prompt()
+    {
+        Ok(answer) => Ok(answer),
+        Err(WizardError::Canceled) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// The command entry at the end of `path`, and the scope to run it in,
+/// if it's still there and the guards along the way let it be run.
+fn find<'a>(entries: &'a [Entry], path: &[String], scope: &Scope) -> Option<(&'a Entry, Scope)> {
+    let (label, rest) = path.split_first()?;
+    let entry = entries.iter().find(|e| !e.separator && e.label == *label)?;
+    let inner = scope.enter(entry);
+    if let Some(guard) = &entry.guard {
+        if !inner.succeeds(guard) {
+            return None;
+        }
+    }
+    match rest.is_empty() {
+        true => entry.command.as_ref().map(|_| (entry, inner)),
+        false => find(&entry.entries, rest, &inner),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_separators_inside_commands() {
+        let menu = Menu::parse_entries("Main", ["Set x = export X=1 = 2"]).unwrap();
+        assert_eq!(menu.entries[0].label, "Set x");
+        assert!(matches!(
+            &menu.entries[0].command,
+            Some(EntryCommand::Shell(c)) if c == "export X=1 = 2"
+        ));
+        assert!(matches!(
+            Menu::parse_entries("Main", ["no separator"]),
+            Err(WizardError::InvalidConfiguration { .. })
+        ));
+    }
+
+    #[test]
+    fn checks_each_entry_does_one_thing() {
+        let mut menu: Menu = toml::from_str(
+            r#"
+            [[entries]]
+            label = "Services"
+            cwd = "services"
+            entries = [{ label = "List", command = ["ls", "-l"] }, { label = "Back", back = true }]
+            "#,
+        )
+        .unwrap();
+        check("", &mut menu.entries, Path::new("/etc/ops")).unwrap();
+        assert_eq!(
+            menu.entries[0].cwd,
+            Some(PathBuf::from("/etc/ops/services"))
+        );
+
+        let mut both = vec![Entry {
+            label: "Both".to_string(),
+            command: Some(EntryCommand::Shell("true".to_string())),
+            back: true,
+            ..Default::default()
+        }];
+        assert!(check("", &mut both, Path::new("")).is_err());
+        let mut empty = vec![Entry {
+            label: "Empty".to_string(),
+            command: Some(EntryCommand::Argv(vec![])),
+            ..Default::default()
+        }];
+        assert!(check("", &mut empty, Path::new("")).is_err());
+    }
+
+    #[test]
+    fn checks_keys_and_separators() {
+        let mut menu: Menu = toml::from_str(
+            r#"
+            entries = [
+              { separator = true, label = "Database" },
+              { label = "Migrate", key = "m", command = "true" },
+              { label = "Drop", key = "m", command = "true" },
+            ]
+            "#,
+        )
+        .unwrap();
+        assert!(check("", &mut menu.entries, Path::new("")).is_err());
+        menu.entries[2].key = Some('d');
+        check("", &mut menu.entries, Path::new("")).unwrap();
+
+        menu.entries[0].confirm = Some("Sure?".to_string());
+        assert!(check("", &mut menu.entries, Path::new("")).is_err());
+        let mut separators = vec![Entry {
+            separator: true,
+            ..Default::default()
+        }];
+        assert!(check("", &mut separators, Path::new("")).is_err());
+    }
+
+    #[test]
+    fn picks_entries_by_key() {
+        use crate::backend::{with_backend, ScriptedBackend};
+        use serde_json::json;
+        use std::rc::Rc;
+
+        let menu: Menu = toml::from_str(
+            r#"
+            entries = [
+              { label = "Migrate", key = "m", command = "exit 3" },
+              { label = "Drop", key = "d", command = "exit 2" },
+            ]
+            "#,
+        )
+        .unwrap();
+        let script = Rc::new(ScriptedBackend::new([json!({ "key": "d" })]));
+        let exit = with_backend(script, || menu.run("", false, &mut History::default())).unwrap();
+        assert!(matches!(exit, MenuExit::Quit));
+
+        let script = Rc::new(ScriptedBackend::new([json!({ "key": "x" })]));
+        assert!(with_backend(script, || menu.run("", false, &mut History::default())).is_err());
+    }
+
+    #[test]
+    fn gates_entries_behind_confirm_and_guards() {
+        use crate::backend::{with_backend, ScriptedBackend};
+        use serde_json::json;
+        use std::rc::Rc;
+
+        let menu: Menu = toml::from_str(
+            r#"
+            entries = [
+              { separator = true, label = "Danger" },
+              { label = "Drop", command = "exit 3", confirm = "Really drop?" },
+              { label = "Hidden", command = "exit 3", guard = "false", unavailable = "hide" },
+              { label = "Disabled", command = "exit 3", guard = ["false"] },
+              { label = "Quit", command = "exit 2" },
+            ]
+            "#,
+        )
+        .unwrap();
+        let script = Rc::new(ScriptedBackend::new([
+            json!("Drop"),
+            json!("no"),
+            json!("Quit"),
+        ]));
+        let exit = with_backend(script.clone(), || {
+            menu.run("", false, &mut History::default())
+        })
+        .unwrap();
+        assert!(matches!(exit, MenuExit::Quit));
+        assert_eq!(script.remaining(), 0);
+
+        // Neither the heading nor an entry whose guard fails can be chosen:
+        for unavailable in ["── Danger ──", "Disabled (unavailable)"] {
+            let script = Rc::new(ScriptedBackend::new([json!(unavailable)]));
+            let chosen = with_backend(script, || menu.run("", false, &mut History::default()));
+            assert!(
+                matches!(chosen, Err(WizardError::InvalidAnswer { .. })),
+                "{}",
+                unavailable
+            );
+        }
+
+        let hidden = Rc::new(ScriptedBackend::new([json!("Hidden")]));
+        assert!(with_backend(hidden, || menu.run("", false, &mut History::default())).is_err());
+    }
+
+    #[test]
+    fn asks_even_with_canned_answers() {
+        use crate::answers;
+        use crate::backend::{with_backend, ScriptedBackend};
+        use serde_json::json;
+        use std::rc::Rc;
+
+        let menu: Menu = toml::from_str(
+            r#"
+            entries = [
+              { label = "Drop", command = "exit 3", confirm = "Really drop?" },
+              { label = "Quit", command = "exit 2" },
+            ]
+            "#,
+        )
+        .unwrap();
+        // Neither the menu nor its confirm takes the canned answer:
+        std::env::set_var("SCRIPT_WIZARD_ANSWER_ASKS_EVEN_WITH_CANNED_ANSWERS", "yes");
+        let script = Rc::new(ScriptedBackend::new([
+            json!("Drop"),
+            json!("no"),
+            json!("Quit"),
+        ]));
+        let exit = answers::with_key("asks-even-with-canned-answers", || {
+            with_backend(script.clone(), || {
+                menu.run("", false, &mut History::default())
+            })
+        });
+        std::env::remove_var("SCRIPT_WIZARD_ANSWER_ASKS_EVEN_WITH_CANNED_ANSWERS");
+        assert!(matches!(exit, Ok(MenuExit::Quit)));
+        assert_eq!(script.remaining(), 0);
+    }
+
+    #[test]
+    fn splits_words_without_a_shell() {
+        assert_eq!(
+            split_words(r#"git commit -m "it's \"done\"" 'a  b' c\ d"#).unwrap(),
+            ["git", "commit", "-m", r#"it's "done""#, "a  b", "c d"]
+        );
+        assert!(split_words("echo 'open").is_err());
+        assert!(split_words("  ").is_err());
+    }
+
+    #[test]
+    fn stays_or_aborts_on_failure() {
+        use crate::backend::{with_backend, ScriptedBackend};
+        use serde_json::json;
+        use std::rc::Rc;
+
+        let mut menu = Menu::parse_entries("Main", ["Fail = exit 3", "Quit = exit 2"]).unwrap();
+        let aborted = with_backend(Rc::new(ScriptedBackend::new([json!("Fail")])), || {
+            menu.run("", false, &mut History::default())
+        });
+        assert!(matches!(
+            aborted,
+            Err(WizardError::CommandFailed { code: 3 })
+        ));
+
+        menu.on_failure = Some(OnFailure::Stay);
+        menu.shell = Some(Shell::Sh);
+        let script = Rc::new(ScriptedBackend::new([
+            json!("Fail"),
+            json!("yes"),
+            json!("no"),
+            json!("Quit"),
+        ]));
+        let exit = with_backend(script.clone(), || {
+            menu.run("", false, &mut History::default())
+        })
+        .unwrap();
+        assert!(matches!(exit, MenuExit::Quit));
+        assert_eq!(script.remaining(), 0);
+    }
+
+    #[test]
+    fn pages_the_output_of_commands() {
+        use crate::backend::{with_backend, ScriptedBackend};
+        use serde_json::json;
+        use std::rc::Rc;
+
+        let menu: Menu = toml::from_str(
+            r#"
+            pager = true
+            entries = [
+              { label = "Both", command = "echo out; echo err >&2; echo out again" },
+              { label = "Quiet", command = "true", pager = false },
+              { label = "Quit", command = "exit 2" },
+            ]
+            "#,
+        )
+        .unwrap();
+        let script = Rc::new(ScriptedBackend::new([
+            json!("Both"),
+            json!("Quiet"),
+            json!("Quit"),
+        ]));
+        let exit = with_backend(script.clone(), || {
+            menu.run("", false, &mut History::default())
+        })
+        .unwrap();
+        assert!(matches!(exit, MenuExit::Quit));
+        let paged = script.paged();
+        assert_eq!(
+            paged[0],
+            ("out\nerr\nout again\n".to_string(), "exit 0".to_string())
+        );
+        assert_eq!(paged[1].1, "exit 2");
+        assert_eq!(paged.len(), 2);
+    }
+
+    #[test]
+    fn offers_recent_commands_again() {
+        use crate::backend::{with_backend, ScriptedBackend};
+        use serde_json::json;
+        use std::rc::Rc;
+
+        let dir = std::env::temp_dir().join(format!("script-wizard-recent-{}", std::process::id()));
+        let log = dir.join("log");
+        std::fs::create_dir_all(&dir).unwrap();
+        let menu: Menu = toml::from_str(&format!(
+            r#"
+            [[entries]]
+            label = "Services"
+            entries = [{{ label = "Restart", command = "echo restarted >> '{}'" }}]
+            "#,
+            log.display()
+        ))
+        .unwrap();
+        let history = || History::at(dir.join("history.json"), "ops");
+        let first = Rc::new(ScriptedBackend::new([
+            json!("Services"),
+            json!("Restart"),
+            json!(null),
+            json!(null),
+        ]));
+        let canceled = with_backend(first, || menu.run("", false, &mut history()));
+        assert!(matches!(canceled, Err(WizardError::Canceled)));
+
+        // The next time, it's at the top of the menu, and r runs it:
+        let second = Rc::new(ScriptedBackend::new([json!({ "key": "r" }), json!(null)]));
+        let canceled = with_backend(second.clone(), || menu.run("", false, &mut history()));
+        assert!(matches!(canceled, Err(WizardError::Canceled)));
+        assert_eq!(second.remaining(), 0);
+        assert_eq!(
+            std::fs::read_to_string(&log).unwrap(),
+            "restarted\nrestarted\n"
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
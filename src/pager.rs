@@ -0,0 +1,230 @@
+//! A viewer for the output of menu commands, that can be scrolled and
+//! searched before going back to the menu.
+
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::{Attribute, Print, SetAttribute};
+use crossterm::{cursor, execute, queue, terminal};
+use std::io::{self, Write};
+
+/// Show `text` on the terminal until the user closes it, with `title`
+/// and `status` in the footer.
+pub(crate) fn show(title: &str, text: &str, status: &str) -> io::Result<()> {
+    let mut lines: Vec<String> = text.lines().map(clean).collect();
+    if lines.is_empty() {
+        lines.push("(no output)".to_string());
+    }
+    let mut pager = Pager {
+        title,
+        status,
+        lines,
+        top: 0,
+        pattern: None,
+        input: None,
+        message: None,
+    };
+    let mut stderr = io::stderr();
+    terminal::enable_raw_mode()?;
+    execute!(stderr, terminal::EnterAlternateScreen, cursor::Hide)?;
+    let result = pager.run(&mut stderr);
+    let _ = execute!(stderr, terminal::LeaveAlternateScreen, cursor::Show);
+    terminal::disable_raw_mode()?;
+    result
+}
+
+struct Pager<'a> {
+    title: &'a str,
+    status: &'a str,
+    lines: Vec<String>,
+    /// The first row shown
+    top: usize,
+    /// What `n` and `N` search for
+    pattern: Option<String>,
+    /// The search being typed after `/`
+    input: Option<String>,
+    /// Shown in the footer until the next key
+    message: Option<String>,
+}
+
+impl Pager<'_> {
+    fn run(&mut self, out: &mut impl Write) -> io::Result<()> {
+        loop {
+            let (width, height) = terminal::size()?;
+            let rows = wrap(&self.lines, width.max(1) as usize);
+            let page = (height as usize).saturating_sub(1).max(1);
+            let last = rows.len().saturating_sub(page);
+            self.top = self.top.min(last);
+            self.draw(out, &rows, page)?;
+
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind == KeyEventKind::Release {
+                continue;
+            }
+            self.message = None;
+            if let Some(input) = &mut self.input {
+                match key.code {
+                    KeyCode::Enter => {
+                        let pattern = std::mem::take(input);
+                        self.input = None;
+                        if !pattern.is_empty() {
+                            self.pattern = Some(pattern);
+                        }
+                        self.find(&rows, self.top, true);
+                    }
+                    KeyCode::Esc => self.input = None,
+                    KeyCode::Backspace => {
+                        input.pop();
+                    }
+                    KeyCode::Char(c) => input.push(c),
+                    _ => {}
+                }
+                continue;
+            }
+            match key {
+                KeyEvent {
+                    code: KeyCode::Char('c'),
+                    modifiers: KeyModifiers::CONTROL,
+                    ..
+                } => return Ok(()),
+                KeyEvent { code, .. } => match code {
+                    KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                    KeyCode::Down | KeyCode::Enter | KeyCode::Char('j') => self.top += 1,
+                    KeyCode::Up | KeyCode::Char('k') => self.top = self.top.saturating_sub(1),
+                    KeyCode::PageDown | KeyCode::Char(' ') | KeyCode::Char('f') => self.top += page,
+                    KeyCode::PageUp | KeyCode::Char('b') => {
+                        self.top = self.top.saturating_sub(page)
+                    }
+                    KeyCode::Home | KeyCode::Char('g') => self.top = 0,
+                    KeyCode::End | KeyCode::Char('G') => self.top = last,
+                    KeyCode::Char('/') => self.input = Some(String::new()),
+                    KeyCode::Char('n') => self.find(&rows, self.top + 1, true),
+                    KeyCode::Char('N') => self.find(&rows, self.top, false),
+                    _ => {}
+                },
+            }
+        }
+    }
+
+    /// Scroll to the next row containing the pattern, starting at row
+    /// `from` and going down, or else the previous one above it.
+    fn find(&mut self, rows: &[String], from: usize, down: bool) {
+        let Some(pattern) = &self.pattern else {
+            self.message = Some("Type / to search".to_string());
+            return;
+        };
+        let matches = |i: &usize| rows[*i].contains(pattern.as_str());
+        let found = match down {
+            true => (from..rows.len()).find(matches),
+            false => (0..from.min(rows.len())).rev().find(matches),
+        };
+        match found {
+            Some(row) => self.top = row,
+            None => self.message = Some(format!("Pattern not found: {}", pattern)),
+        }
+    }
+
+    fn draw(&self, out: &mut impl Write, rows: &[String], page: usize) -> io::Result<()> {
+        queue!(out, cursor::MoveTo(0, 0))?;
+        for y in 0..page {
+            queue!(out, cursor::MoveTo(0, y as u16))?;
+            if let Some(row) = rows.get(self.top + y) {
+                self.draw_row(out, row)?;
+            }
+            queue!(out, terminal::Clear(terminal::ClearType::UntilNewLine))?;
+        }
+        let footer = match (&self.input, &self.message) {
+            (Some(input), _) => format!("/{}", input),
+            (None, Some(message)) => message.clone(),
+            (None, None) => format!(
+                "{} | {} | {}-{} of {} | q: back, /: search, n/N: next/previous",
+                self.title,
+                self.status,
+                (self.top + 1).min(rows.len()),
+                (self.top + page).min(rows.len()),
+                rows.len()
+            ),
+        };
+        let (width, _) = terminal::size()?;
+        let footer: String = footer.chars().take(width as usize).collect();
+        queue!(
+            out,
+            cursor::MoveTo(0, page as u16),
+            SetAttribute(Attribute::Reverse),
+            Print(footer),
+            terminal::Clear(terminal::ClearType::UntilNewLine),
+            SetAttribute(Attribute::Reset)
+        )?;
+        out.flush()
+    }
+
+    /// Print a row with every match of the pattern highlighted.
+    fn draw_row(&self, out: &mut impl Write, row: &str) -> io::Result<()> {
+        let pattern = self.pattern.as_deref().filter(|p| !p.is_empty());
+        let mut rest = row;
+        while let Some(at) = pattern.and_then(|p| rest.find(p)) {
+            let end = at + pattern.map_or(0, str::len);
+            queue!(
+                out,
+                Print(&rest[..at]),
+                SetAttribute(Attribute::Reverse),
+                Print(&rest[at..end]),
+                SetAttribute(Attribute::Reset)
+            )?;
+            rest = &rest[end..];
+        }
+        queue!(out, Print(rest))
+    }
+}
+
+/// Remove escape sequences and other control characters from a line of
+/// output, and expand its tabs.
+fn clean(line: &str) -> String {
+    let mut cleaned = String::new();
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\x1b' => {
+                // Skip a CSI sequence up to its final byte, or else
+                // the one character after the escape:
+                if chars.next_if_eq(&'[').is_some() {
+                    while chars.next().is_some_and(|c| !('@'..='~').contains(&c)) {}
+                } else {
+                    chars.next();
+                }
+            }
+            '\t' => {
+                let width = cleaned.chars().count();
+                cleaned.push_str(&" ".repeat(8 - width % 8));
+            }
+            c if c.is_control() => {}
+            c => cleaned.push(c),
+        }
+    }
+    cleaned
+}
+
+/// Split lines longer than `width` into rows.
+fn wrap(lines: &[String], width: usize) -> Vec<String> {
+    let mut rows = Vec::new();
+    for line in lines {
+        let chars: Vec<char> = line.chars().collect();
+        if chars.is_empty() {
+            rows.push(String::new());
+        }
+        rows.extend(chars.chunks(width).map(|chunk| chunk.iter().collect()));
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cleans_and_wraps_output() {
+        assert_eq!(clean("\x1b[1;31merror\x1b[0m:\tdone\r"), "error:  done");
+        let lines = vec!["abcdefg".to_string(), String::new(), "hi".to_string()];
+        assert_eq!(wrap(&lines, 3), ["abc", "def", "g", "", "hi"]);
+    }
+}
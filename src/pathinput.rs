@@ -0,0 +1,266 @@
+//! The prompt behind `path`: a line of input like inquire's, with
+//! names from the filesystem suggested below it, where Ctrl-T shows or
+//! hides hidden files.
+
+use crate::ask::PathCompleter;
+use crate::rawprompt::{self, print, render_config, styled, Action, KeyPrompt};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use crossterm::style::Print;
+use crossterm::{cursor, queue};
+use inquire::autocompletion::{Autocomplete, Replacement};
+use inquire::error::{InquireError, InquireResult};
+use inquire::validator::{ErrorMessage, StringValidator, Validation};
+use std::io::{self, Write};
+
+/// How many suggestions are shown at once.
+const PAGE_SIZE: usize = 7;
+
+pub(crate) struct PathInput<'a> {
+    question: &'a str,
+    default: Option<&'a str>,
+    help: Option<&'a str>,
+    validator: Option<Box<dyn StringValidator>>,
+    completer: PathCompleter,
+    input: String,
+    suggestions: Vec<String>,
+    /// The highlighted suggestion, if any
+    cursor: Option<usize>,
+    /// The first suggestion on the page
+    top: usize,
+    /// Why the last answer wasn't accepted
+    error: Option<String>,
+}
+
+impl<'a> PathInput<'a> {
+    pub(crate) fn new(question: &'a str, completer: PathCompleter) -> Self {
+        PathInput {
+            question,
+            default: None,
+            help: None,
+            validator: None,
+            completer,
+            input: String::new(),
+            suggestions: vec![],
+            cursor: None,
+            top: 0,
+            error: None,
+        }
+    }
+
+    pub(crate) fn default(mut self, default: Option<&'a str>) -> Self {
+        self.default = default;
+        self
+    }
+
+    pub(crate) fn help(mut self, help: Option<&'a str>) -> Self {
+        self.help = help;
+        self
+    }
+
+    pub(crate) fn validator(mut self, validator: Option<Box<dyn StringValidator>>) -> Self {
+        self.validator = validator;
+        self
+    }
+
+    /// Start with `input` already typed.
+    pub(crate) fn input(mut self, input: Option<String>) -> Self {
+        self.input = input.unwrap_or_default();
+        self
+    }
+
+    /// Ask on the terminal, and return the answer.
+    pub(crate) fn prompt(mut self) -> InquireResult<String> {
+        self.suggest()?;
+        rawprompt::prompt(self)
+    }
+
+    /// List the suggestions for the input as it is now.
+    fn suggest(&mut self) -> InquireResult<()> {
+        self.suggestions = self
+            .completer
+            .get_suggestions(&self.input)
+            .map_err(InquireError::Custom)?;
+        self.cursor = None;
+        self.top = 0;
+        Ok(())
+    }
+}
+
+impl KeyPrompt for PathInput<'_> {
+    type Answer = String;
+
+    fn question(&self) -> &str {
+        self.question
+    }
+
+    fn handle(&mut self, key: KeyEvent) -> InquireResult<Action<String>> {
+        if key.modifiers.contains(KeyModifiers::CONTROL) {
+            match key.code {
+                KeyCode::Char('c') => return Ok(Action::Interrupt),
+                KeyCode::Char('t') => {
+                    self.completer.toggle_hidden();
+                    self.suggest()?;
+                }
+                _ => {}
+            }
+            return Ok(Action::Redraw);
+        }
+        match key.code {
+            KeyCode::Esc => return Ok(Action::Cancel),
+            KeyCode::Enter => {
+                let highlighted = self.cursor.map(|i| self.suggestions[i].clone());
+                let answer = match (highlighted, self.input.as_str(), self.default) {
+                    (Some(highlighted), _, _) => highlighted,
+                    (None, "", Some(default)) => default.to_string(),
+                    (None, input, _) => input.to_string(),
+                };
+                let validation = match &self.validator {
+                    Some(validator) => validator.validate(&answer).map_err(InquireError::Custom)?,
+                    None => Validation::Valid,
+                };
+                match validation {
+                    Validation::Valid => return Ok(Action::Done(answer)),
+                    Validation::Invalid(ErrorMessage::Custom(reason)) => self.error = Some(reason),
+                    Validation::Invalid(ErrorMessage::Default) => {
+                        self.error = Some("Invalid input".to_string())
+                    }
+                }
+            }
+            KeyCode::Tab => {
+                let highlighted = self.cursor.map(|i| self.suggestions[i].clone());
+                let completion = self
+                    .completer
+                    .get_completion(&self.input, highlighted)
+                    .map_err(InquireError::Custom)?;
+                if let Replacement::Some(completion) = completion {
+                    self.input = completion;
+                    self.error = None;
+                    self.suggest()?;
+                }
+            }
+            KeyCode::Up => {
+                self.cursor = match self.cursor {
+                    Some(0) | None => None,
+                    Some(i) => Some(i - 1),
+                };
+            }
+            KeyCode::Down => {
+                let last = self.suggestions.len().checked_sub(1);
+                self.cursor = match self.cursor {
+                    None => last.map(|_| 0),
+                    Some(i) => Some((i + 1).min(last.unwrap_or(0))),
+                };
+            }
+            KeyCode::Backspace => {
+                self.input.pop();
+                self.error = None;
+                self.suggest()?;
+            }
+            KeyCode::Char(c) => {
+                self.input.push(c);
+                self.error = None;
+                self.suggest()?;
+            }
+            _ => {}
+        }
+        Ok(Action::Redraw)
+    }
+
+    fn draw(&mut self, out: &mut impl Write) -> io::Result<usize> {
+        let config = render_config();
+        let width = rawprompt::width()?;
+
+        // Keep the highlighted suggestion on the page:
+        let cursor = self.cursor.unwrap_or(0);
+        if cursor < self.top {
+            self.top = cursor;
+        } else if cursor >= self.top + PAGE_SIZE {
+            self.top = cursor + 1 - PAGE_SIZE;
+        }
+
+        let (input, default) = (&self.input, self.default);
+        let column = rawprompt::print_question(out, self.question, input, default, usize::MAX)?;
+        let mut lines = 1;
+        if let Some(error) = &self.error {
+            rawprompt::print_error(out, error)?;
+            lines += 1;
+        }
+        let page = self.top..(self.top + PAGE_SIZE).min(self.suggestions.len());
+        for position in page {
+            let (prefix, style) = match Some(position) == self.cursor {
+                true => (
+                    &config.highlighted_option_prefix,
+                    config.selected_option.unwrap_or(config.option),
+                ),
+                false => (&config.unhighlighted_option_prefix, config.option),
+            };
+            let suggestion = &self.suggestions[position];
+            queue!(out, Print("\r\n"))?;
+            print(
+                out,
+                &[styled(prefix), (" ", config.option), (suggestion, style)],
+                width,
+            )?;
+            lines += 1;
+        }
+        if let Some(help) = self.help {
+            rawprompt::print_help(out, help, width)?;
+            lines += 1;
+        }
+        // Put the cursor back at the end of the input:
+        if lines > 1 {
+            queue!(out, cursor::MoveUp(lines as u16 - 1))?;
+        }
+        queue!(out, cursor::MoveToColumn(column as u16))?;
+        Ok(0)
+    }
+
+    fn show(&self, answer: &String) -> String {
+        answer.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(input: &mut PathInput, code: KeyCode, modifiers: KeyModifiers) -> Action<String> {
+        input.handle(KeyEvent::new(code, modifiers)).unwrap()
+    }
+
+    #[test]
+    fn toggles_hidden_files() {
+        let dir = std::env::temp_dir().join(format!("script-wizard-hidden-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("conf")).unwrap();
+        std::fs::write(dir.join(".env"), "").unwrap();
+        std::fs::write(dir.join("app.toml"), "").unwrap();
+        let typed = format!("{}/", dir.display());
+        let mut input =
+            PathInput::new("Config?", PathCompleter::default()).input(Some(typed.clone()));
+        input.suggest().unwrap();
+        let names = |input: &PathInput| -> Vec<String> {
+            input
+                .suggestions
+                .iter()
+                .map(|s| s[typed.len()..].to_string())
+                .collect()
+        };
+        assert_eq!(names(&input), ["app.toml", "conf/"]);
+        press(&mut input, KeyCode::Char('t'), KeyModifiers::CONTROL);
+        assert_eq!(names(&input), [".env", "app.toml", "conf/"]);
+
+        // Pick a suggestion with the arrows, or complete it with tab:
+        press(&mut input, KeyCode::Down, KeyModifiers::NONE);
+        press(&mut input, KeyCode::Down, KeyModifiers::NONE);
+        assert_eq!(
+            press(&mut input, KeyCode::Tab, KeyModifiers::NONE),
+            Action::Redraw
+        );
+        assert_eq!(input.input, format!("{}app.toml", typed));
+        press(&mut input, KeyCode::Char('t'), KeyModifiers::CONTROL);
+        assert_eq!(names(&input), ["app.toml"]);
+        let done = press(&mut input, KeyCode::Enter, KeyModifiers::NONE);
+        assert_eq!(done, Action::Done(format!("{}app.toml", typed)));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
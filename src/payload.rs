@@ -0,0 +1,574 @@
+//! The payload formats the pod can speak: the `args`, `value` and
+//! `ex-data` fields of pod messages are encoded as JSON, EDN or
+//! transit+json strings.
+
+use clap::ValueEnum;
+use serde_json::{Map, Number, Value as JsonValue};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// Plain JSON: keywords arrive as strings (the default)
+    #[default]
+    Json,
+    /// EDN, so keywords stay keywords
+    Edn,
+    /// Transit over JSON, so keywords stay keywords
+    #[value(name = "transit+json")]
+    TransitJson,
+}
+
+/// Data to send to the client. Unlike JSON it has keywords, which the
+/// EDN and transit formats keep.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Data {
+    Nil,
+    Bool(bool),
+    Number(Number),
+    String(String),
+    Keyword(String),
+    Vector(Vec<Data>),
+    Map(Vec<(Data, Data)>),
+}
+
+/// JSON as Clojure would like it: map keys become keywords.
+impl From<JsonValue> for Data {
+    fn from(value: JsonValue) -> Self {
+        match value {
+            JsonValue::Null => Data::Nil,
+            JsonValue::Bool(b) => Data::Bool(b),
+            JsonValue::Number(n) => Data::Number(n),
+            JsonValue::String(s) => Data::String(s),
+            JsonValue::Array(items) => Data::Vector(items.into_iter().map(Data::from).collect()),
+            JsonValue::Object(map) => Data::Map(
+                map.into_iter()
+                    .map(|(k, v)| (Data::Keyword(k), Data::from(v)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl Format {
+    /// The name of the format in the pod protocol.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Format::Json => "json",
+            Format::Edn => "edn",
+            Format::TransitJson => "transit+json",
+        }
+    }
+
+    /// Decode a payload from the client as JSON. Keywords and symbols
+    /// become their names, and sets become arrays.
+    pub fn decode(&self, payload: &str) -> Result<JsonValue, String> {
+        match self {
+            Format::Json => serde_json::from_str(payload).map_err(|e| e.to_string()),
+            Format::Edn => {
+                let mut parser = EdnParser {
+                    input: payload.as_bytes(),
+                    pos: 0,
+                };
+                let value = parser.value()?.ok_or("empty EDN payload")?;
+                match parser.value()? {
+                    None => Ok(value),
+                    Some(_) => Err("more than one EDN value in the payload".to_string()),
+                }
+            }
+            Format::TransitJson => {
+                let json: JsonValue = serde_json::from_str(payload).map_err(|e| e.to_string())?;
+                TransitReader::default().value(json, false)
+            }
+        }
+    }
+
+    /// Encode `data` to send to the client.
+    pub fn encode(&self, data: &Data) -> String {
+        match self {
+            Format::Json => to_json(data).to_string(),
+            Format::Edn => {
+                let mut out = String::new();
+                write_edn(data, &mut out);
+                out
+            }
+            Format::TransitJson => match data {
+                // Transit only allows arrays and maps at the top level:
+                Data::Vector(_) | Data::Map(_) => to_transit(data, false).to_string(),
+                _ => serde_json::json!(["~#'", to_transit(data, false)]).to_string(),
+            },
+        }
+    }
+}
+
+fn to_json(data: &Data) -> JsonValue {
+    match data {
+        Data::Nil => JsonValue::Null,
+        Data::Bool(b) => JsonValue::Bool(*b),
+        Data::Number(n) => JsonValue::Number(n.clone()),
+        Data::String(s) | Data::Keyword(s) => JsonValue::String(s.clone()),
+        Data::Vector(items) => items.iter().map(to_json).collect(),
+        Data::Map(entries) => JsonValue::Object(
+            entries
+                .iter()
+                .map(|(k, v)| (key_name(&to_json(k)), to_json(v)))
+                .collect(),
+        ),
+    }
+}
+
+/// A map key as a JSON object key.
+fn key_name(key: &JsonValue) -> String {
+    match key {
+        JsonValue::String(s) => s.clone(),
+        k => k.to_string(),
+    }
+}
+
+fn write_edn(data: &Data, out: &mut String) {
+    match data {
+        Data::Nil => out.push_str("nil"),
+        Data::Bool(b) => out.push_str(&b.to_string()),
+        Data::Number(n) => out.push_str(&n.to_string()),
+        // JSON string escapes are valid EDN, except `\/` which serde never writes:
+        Data::String(s) => out.push_str(&JsonValue::String(s.clone()).to_string()),
+        Data::Keyword(k) => {
+            out.push(':');
+            out.push_str(k);
+        }
+        Data::Vector(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(' ');
+                }
+                write_edn(item, out);
+            }
+            out.push(']');
+        }
+        Data::Map(entries) => {
+            out.push('{');
+            for (i, (k, v)) in entries.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                write_edn(k, out);
+                out.push(' ');
+                write_edn(v, out);
+            }
+            out.push('}');
+        }
+    }
+}
+
+struct EdnParser<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl EdnParser<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.pos).copied()
+    }
+
+    fn error(&self, message: &str) -> String {
+        format!("{} at byte {} of the EDN payload", message, self.pos)
+    }
+
+    /// Skip whitespace, commas and comments.
+    fn skip_blank(&mut self) {
+        while let Some(b) = self.peek() {
+            match b {
+                b' ' | b'\t' | b'\r' | b'\n' | b',' => self.pos += 1,
+                b';' => {
+                    while !matches!(self.peek(), None | Some(b'\n')) {
+                        self.pos += 1;
+                    }
+                }
+                _ => return,
+            }
+        }
+    }
+
+    /// A symbol, keyword or number token.
+    fn token(&mut self) -> &str {
+        let start = self.pos;
+        while let Some(b) = self.peek() {
+            if b.is_ascii_whitespace() || b",()[]{}\";".contains(&b) {
+                break;
+            }
+            self.pos += 1;
+        }
+        std::str::from_utf8(&self.input[start..self.pos]).unwrap_or_default()
+    }
+
+    /// The next value, or `None` at the end of the input.
+    fn value(&mut self) -> Result<Option<JsonValue>, String> {
+        self.skip_blank();
+        let Some(b) = self.peek() else {
+            return Ok(None);
+        };
+        let value = match b {
+            b'"' => JsonValue::String(self.string()?),
+            b'[' | b'(' => {
+                self.pos += 1;
+                JsonValue::Array(self.items(if b == b'[' { b']' } else { b')' })?)
+            }
+            b'{' => {
+                self.pos += 1;
+                let items = self.items(b'}')?;
+                if items.len() % 2 != 0 {
+                    return Err(self.error("map with an odd number of forms"));
+                }
+                let mut map = Map::new();
+                for pair in items.chunks(2) {
+                    map.insert(key_name(&pair[0]), pair[1].clone());
+                }
+                JsonValue::Object(map)
+            }
+            b'#' => {
+                self.pos += 1;
+                match self.peek() {
+                    Some(b'{') => {
+                        self.pos += 1;
+                        JsonValue::Array(self.items(b'}')?)
+                    }
+                    Some(b'_') => {
+                        // Discard the next form:
+                        self.pos += 1;
+                        self.value()?;
+                        return self.value();
+                    }
+                    _ => {
+                        // A tagged literal, eg. #inst "...": keep the value.
+                        self.token();
+                        return match self.value()? {
+                            Some(v) => Ok(Some(v)),
+                            None => Err(self.error("tag without a value")),
+                        };
+                    }
+                }
+            }
+            b'\\' => {
+                self.pos += 1;
+                let c = match self.token() {
+                    "newline" => "\n".to_string(),
+                    "space" => " ".to_string(),
+                    "tab" => "\t".to_string(),
+                    "return" => "\r".to_string(),
+                    t => t.to_string(),
+                };
+                JsonValue::String(c)
+            }
+            b')' | b']' | b'}' => return Err(self.error("unexpected closing bracket")),
+            _ => {
+                let token = self.token().to_string();
+                if token.is_empty() {
+                    return Err(self.error("unexpected character"));
+                }
+                atom(&token)
+            }
+        };
+        Ok(Some(value))
+    }
+
+    /// The values up to the `close` bracket.
+    fn items(&mut self, close: u8) -> Result<Vec<JsonValue>, String> {
+        let mut items = vec![];
+        loop {
+            self.skip_blank();
+            match self.peek() {
+                None => return Err(self.error("unclosed collection")),
+                Some(b) if b == close => {
+                    self.pos += 1;
+                    return Ok(items);
+                }
+                Some(_) => items.push(
+                    self.value()?
+                        .ok_or_else(|| self.error("unclosed collection"))?,
+                ),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.pos += 1;
+        let mut bytes = vec![];
+        loop {
+            let b = self.peek().ok_or_else(|| self.error("unclosed string"))?;
+            self.pos += 1;
+            match b {
+                b'"' => break,
+                b'\\' => {
+                    let e = self.peek().ok_or_else(|| self.error("unclosed string"))?;
+                    self.pos += 1;
+                    match e {
+                        b'n' => bytes.push(b'\n'),
+                        b't' => bytes.push(b'\t'),
+                        b'r' => bytes.push(b'\r'),
+                        b'u' => {
+                            let hex = self
+                                .input
+                                .get(self.pos..self.pos + 4)
+                                .and_then(|h| std::str::from_utf8(h).ok())
+                                .and_then(|h| u32::from_str_radix(h, 16).ok())
+                                .and_then(char::from_u32)
+                                .ok_or_else(|| self.error("bad unicode escape"))?;
+                            self.pos += 4;
+                            bytes.extend_from_slice(hex.to_string().as_bytes());
+                        }
+                        e => bytes.push(e),
+                    }
+                }
+                b => bytes.push(b),
+            }
+        }
+        String::from_utf8(bytes).map_err(|_| self.error("string is not UTF-8"))
+    }
+}
+
+/// A bare EDN token: nil, a boolean, a number, or a keyword or symbol
+/// (which become their names).
+fn atom(token: &str) -> JsonValue {
+    match token {
+        "nil" => return JsonValue::Null,
+        "true" => return JsonValue::Bool(true),
+        "false" => return JsonValue::Bool(false),
+        _ => {}
+    }
+    let number = token.trim_end_matches(['N', 'M']);
+    if let Ok(i) = number.parse::<i64>() {
+        return JsonValue::from(i);
+    }
+    if number.starts_with(|c: char| c.is_ascii_digit() || c == '-' || c == '+') {
+        if let Some(n) = number.parse::<f64>().ok().and_then(Number::from_f64) {
+            return JsonValue::Number(n);
+        }
+    }
+    JsonValue::String(token.trim_start_matches(':').to_string())
+}
+
+/// Tracks the transit read cache, which replaces repeated map keys,
+/// keywords and symbols with `^` codes.
+#[derive(Default)]
+struct TransitReader {
+    cache: Vec<String>,
+}
+
+/// The number of digits used by transit cache codes.
+const CACHE_DIGITS: usize = 44;
+
+impl TransitReader {
+    /// Resolve a cache code, or remember `s` if it is cacheable.
+    fn cached(&mut self, s: &str, key: bool) -> Result<String, String> {
+        if s.starts_with('^') && s != "^ " && s.len() > 1 {
+            // Each digit is a character from '0' on:
+            let digits: Option<Vec<usize>> = s[1..]
+                .bytes()
+                .map(|b| usize::from(b).checked_sub(48).filter(|&d| d < CACHE_DIGITS))
+                .collect();
+            let index = match digits.as_deref() {
+                Some(&[a]) => a,
+                Some(&[a, b]) => a * CACHE_DIGITS + b,
+                _ => return Err(format!("bad transit cache code {:?}", s)),
+            };
+            return self
+                .cache
+                .get(index)
+                .cloned()
+                .ok_or_else(|| format!("unknown transit cache code {:?}", s));
+        }
+        let cacheable = s.len() > 3
+            && (key || s.starts_with("~:") || s.starts_with("~$") || s.starts_with("~#"));
+        if cacheable {
+            if self.cache.len() == CACHE_DIGITS * CACHE_DIGITS {
+                self.cache.clear();
+            }
+            self.cache.push(s.to_string());
+        }
+        Ok(s.to_string())
+    }
+
+    /// Decode a transit string, eg. `~:keyword`.
+    fn string(&mut self, s: &str, key: bool) -> Result<JsonValue, String> {
+        let s = self.cached(s, key)?;
+        let Some(rest) = s.strip_prefix('~') else {
+            return Ok(JsonValue::String(s));
+        };
+        let (tag, body) = rest.split_at(rest.chars().next().map_or(0, char::len_utf8));
+        Ok(match tag {
+            "~" | "^" | "`" => JsonValue::String(rest.to_string()),
+            ":" | "$" => JsonValue::String(body.to_string()),
+            "_" => JsonValue::Null,
+            "?" => JsonValue::Bool(body == "t"),
+            "i" | "n" => body
+                .parse::<i64>()
+                .map(JsonValue::from)
+                .unwrap_or(JsonValue::String(body.to_string())),
+            "d" | "f" => body
+                .parse::<f64>()
+                .ok()
+                .and_then(Number::from_f64)
+                .map(JsonValue::Number)
+                .unwrap_or(JsonValue::String(body.to_string())),
+            _ => JsonValue::String(body.to_string()),
+        })
+    }
+
+    fn value(&mut self, json: JsonValue, key: bool) -> Result<JsonValue, String> {
+        match json {
+            JsonValue::String(s) => self.string(&s, key),
+            JsonValue::Array(items) => {
+                let mut items = items.into_iter();
+                let Some(first) = items.next() else {
+                    return Ok(JsonValue::Array(vec![]));
+                };
+                match first {
+                    JsonValue::String(s) if s == "^ " => {
+                        let mut map = Map::new();
+                        while let Some(k) = items.next() {
+                            let k = self.value(k, true)?;
+                            let v = items.next().ok_or("map with a key but no value")?;
+                            map.insert(key_name(&k), self.value(v, false)?);
+                        }
+                        Ok(JsonValue::Object(map))
+                    }
+                    JsonValue::String(s) if self.tag(&s)?.is_some() => {
+                        let tag = self.tag(&s)?.unwrap_or_default();
+                        let value = items.next().unwrap_or(JsonValue::Null);
+                        match (tag.as_str(), self.value(value, false)?) {
+                            // A map with keys that aren't strings, as a flat list:
+                            ("cmap", JsonValue::Array(kvs)) => Ok(JsonValue::Object(
+                                kvs.chunks(2)
+                                    .map(|kv| {
+                                        (key_name(&kv[0]), kv.get(1).cloned().unwrap_or_default())
+                                    })
+                                    .collect(),
+                            )),
+                            (_, v) => Ok(v),
+                        }
+                    }
+                    first => {
+                        let mut out = vec![self.value(first, false)?];
+                        for item in items {
+                            out.push(self.value(item, false)?);
+                        }
+                        Ok(JsonValue::Array(out))
+                    }
+                }
+            }
+            JsonValue::Object(map) => {
+                // Verbose transit writes maps as JSON objects:
+                let mut out = Map::new();
+                for (k, v) in map {
+                    let k = self.value(JsonValue::String(k), true)?;
+                    out.insert(key_name(&k), self.value(v, false)?);
+                }
+                Ok(JsonValue::Object(out))
+            }
+            json => Ok(json),
+        }
+    }
+
+    /// The tag of a tagged value like `["~#set", [...]]`, if `s` is one.
+    fn tag(&mut self, s: &str) -> Result<Option<String>, String> {
+        if let Some(tag) = s.strip_prefix("~#") {
+            return Ok(Some(tag.to_string()));
+        }
+        if s.starts_with('^') && s != "^ " {
+            let resolved = self.cached(s, false)?;
+            if let Some(tag) = resolved.strip_prefix("~#") {
+                return Ok(Some(tag.to_string()));
+            }
+        }
+        Ok(None)
+    }
+}
+
+fn to_transit(data: &Data, key: bool) -> JsonValue {
+    match data {
+        Data::Nil if key => JsonValue::from("~_"),
+        Data::Nil => JsonValue::Null,
+        Data::Bool(b) if key => JsonValue::from(if *b { "~?t" } else { "~?f" }),
+        Data::Bool(b) => JsonValue::Bool(*b),
+        Data::Number(n) if key => {
+            JsonValue::from(format!("~{}{}", if n.is_f64() { "d" } else { "i" }, n))
+        }
+        // Integers too big for a JavaScript number go as strings:
+        Data::Number(n) if n.as_i64().is_some_and(|i| i.unsigned_abs() >= 1 << 53) => {
+            JsonValue::from(format!("~i{}", n))
+        }
+        Data::Number(n) => JsonValue::Number(n.clone()),
+        Data::String(s) if s.starts_with(['~', '^', '`']) => JsonValue::from(format!("~{}", s)),
+        Data::String(s) => JsonValue::from(s.as_str()),
+        Data::Keyword(k) => JsonValue::from(format!("~:{}", k)),
+        Data::Vector(items) => items.iter().map(|i| to_transit(i, false)).collect(),
+        Data::Map(entries) => {
+            let mut out = vec![JsonValue::from("^ ")];
+            for (k, v) in entries {
+                out.push(to_transit(k, true));
+                out.push(to_transit(v, false));
+            }
+            JsonValue::Array(out)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn decodes_edn_keywords_and_sets() {
+        let args = Format::Edn
+            .decode("[\"Continue?\" {:default :yes, :tags #{\"a\" \"b\"}} nil 1.5 \\x] ; done")
+            .unwrap();
+        assert_eq!(
+            args,
+            json!(["Continue?", {"default": "yes", "tags": ["a", "b"]}, null, 1.5, "x"])
+        );
+        assert!(Format::Edn.decode("[1 2").is_err());
+        assert!(Format::Edn.decode("{:a}").is_err());
+    }
+
+    #[test]
+    fn encodes_edn() {
+        let data = Data::from(json!({"error": "canceled", "answers": ["a\"b", 1, true, null]}));
+        assert_eq!(
+            Format::Edn.encode(&data),
+            r#"{:error "canceled", :answers ["a\"b" 1 true nil]}"#
+        );
+        assert_eq!(Format::Edn.encode(&Data::Keyword("yes".into())), ":yes");
+    }
+
+    #[test]
+    fn decodes_transit_with_the_cache() {
+        // Written by transit-clj: the second "~:default" key is cached as "^0".
+        let args = Format::TransitJson
+            .decode(
+                r#"["Pick",[["^ ","~:default","~:yes"],["^ ","^0","~~tilde"]],["~#set",["x"]]]"#,
+            )
+            .unwrap();
+        assert_eq!(
+            args,
+            json!(["Pick", [{"default": "yes"}, {"default": "~tilde"}], ["x"]])
+        );
+        for code in ["^!", "^0!", "^\u{7f}", "^000", "^9"] {
+            let payload = format!(r#"[["^ ","~:default","~:yes"],["^ ","{}","x"]]"#, code);
+            assert!(Format::TransitJson.decode(&payload).is_err(), "{}", code);
+        }
+    }
+
+    #[test]
+    fn encodes_transit() {
+        assert_eq!(
+            Format::TransitJson.encode(&Data::String("hi".into())),
+            r#"["~#'","hi"]"#
+        );
+        assert_eq!(
+            Format::TransitJson.encode(&Data::from(json!({"error": "~x"}))),
+            r#"["^ ","~:error","~~x"]"#
+        );
+        let list = Data::Vector(vec![Data::Keyword("a".into()), Data::Nil]);
+        assert_eq!(Format::TransitJson.encode(&list), r#"["~:a",null]"#);
+    }
+}
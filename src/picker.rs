@@ -0,0 +1,429 @@
+//! The list prompt behind `choose` and `select`. It looks and works
+//! like inquire's, but a key shown next to an option picks it with a
+//! single keypress.
+
+use crate::ask::Choice;
+use crate::rawprompt::{self, print, render_config, styled, Action, KeyPrompt};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use crossterm::style::Print;
+use crossterm::{cursor, queue};
+use inquire::error::InquireResult;
+use inquire::Select;
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+/// How many options to show at once
+const PAGE_SIZE: usize = 7;
+
+/// A list of options to choose one of, or with `multi`, any number of.
+pub(crate) struct Picker<'a> {
+    question: &'a str,
+    options: &'a [Choice],
+    multi: bool,
+    help: Option<&'a str>,
+    /// Whether any option has a hotkey. Typed keys are then hotkeys,
+    /// and filtering starts with `/`.
+    hotkeys: bool,
+    /// Whether typed keys go to the filter
+    filtering: bool,
+    filter: String,
+    /// The indexes of the options that match the filter, best first
+    shown: Vec<usize>,
+    /// The highlighted option, as an index into `shown`
+    cursor: usize,
+    /// The first option on the page, as an index into `shown`
+    top: usize,
+    selected: Vec<bool>,
+    /// When to give up, unless a key is pressed before
+    deadline: Option<Instant>,
+}
+
+impl<'a> Picker<'a> {
+    pub(crate) fn new(question: &'a str, options: &'a [Choice], multi: bool) -> Self {
+        let hotkeys = options.iter().any(|o| o.hotkey.is_some() && !o.disabled);
+        let mut picker = Picker {
+            question,
+            options,
+            multi,
+            help: None,
+            hotkeys,
+            filtering: !hotkeys,
+            filter: String::new(),
+            shown: (0..options.len()).collect(),
+            cursor: 0,
+            top: 0,
+            selected: vec![false; options.len()],
+            deadline: None,
+        };
+        picker.move_to(0, true);
+        picker
+    }
+
+    pub(crate) fn help(mut self, help: Option<&'a str>) -> Self {
+        self.help = help;
+        self
+    }
+
+    /// Count down from `timeout` in place of the help message, and
+    /// fail with [`TimedOut`](crate::ask::TimedOut) if it runs out
+    /// before a key is pressed.
+    pub(crate) fn timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.deadline = timeout.map(|timeout| Instant::now() + timeout);
+        self
+    }
+
+    /// Start with the option at `index` highlighted.
+    pub(crate) fn starting_cursor(mut self, index: usize) -> Self {
+        self.move_to(index.min(self.options.len().saturating_sub(1)), true);
+        self
+    }
+
+    /// Start with the options at `indexes` selected.
+    pub(crate) fn selected(mut self, indexes: &[usize]) -> Self {
+        for &i in indexes {
+            if let Some(selected) = self.selected.get_mut(i) {
+                *selected = true;
+            }
+        }
+        self
+    }
+
+    /// Ask on the terminal, and return the indexes of the options
+    /// chosen.
+    pub(crate) fn prompt(self) -> InquireResult<Vec<usize>> {
+        rawprompt::prompt(self)
+    }
+
+    fn press(&mut self, key: KeyEvent) -> Action<Vec<usize>> {
+        // The key that stops the countdown counts like any other:
+        self.deadline = None;
+        if key.modifiers.contains(KeyModifiers::CONTROL) {
+            return match key.code {
+                KeyCode::Char('c') => Action::Interrupt,
+                _ => Action::Redraw,
+            };
+        }
+        let last = self.shown.len().saturating_sub(1);
+        let selectable = self.selectable(self.cursor);
+        match key.code {
+            KeyCode::Esc if self.hotkeys && self.filtering => {
+                self.filtering = false;
+                self.filter.clear();
+                self.refilter();
+            }
+            KeyCode::Esc => return Action::Cancel,
+            KeyCode::Enter if self.multi => {
+                return Action::Done(
+                    (0..self.options.len())
+                        .filter(|&i| self.selected[i])
+                        .collect(),
+                )
+            }
+            KeyCode::Enter if selectable => return Action::Done(vec![self.shown[self.cursor]]),
+            KeyCode::Up => {
+                let above = (0..self.cursor).rev().find(|&p| self.selectable(p));
+                match above {
+                    Some(position) => self.cursor = position,
+                    None => self.move_to(last, false),
+                }
+            }
+            KeyCode::Down => {
+                let below = (self.cursor + 1..self.shown.len()).find(|&p| self.selectable(p));
+                match below {
+                    Some(position) => self.cursor = position,
+                    None => self.move_to(0, true),
+                }
+            }
+            KeyCode::PageUp => self.move_to(self.cursor.saturating_sub(PAGE_SIZE), false),
+            KeyCode::PageDown => self.move_to((self.cursor + PAGE_SIZE).min(last), true),
+            KeyCode::Home => self.move_to(0, true),
+            KeyCode::End => self.move_to(last, false),
+            KeyCode::Char(' ') if self.multi && selectable => {
+                let index = self.shown[self.cursor];
+                self.selected[index] = !self.selected[index];
+            }
+            KeyCode::Right if self.multi => {
+                for &i in &self.shown {
+                    self.selected[i] = !self.options[i].disabled;
+                }
+            }
+            KeyCode::Left if self.multi => {
+                self.shown.iter().for_each(|&i| self.selected[i] = false)
+            }
+            KeyCode::Backspace if self.filtering => {
+                self.filter.pop();
+                self.refilter();
+            }
+            KeyCode::Char('/') if !self.filtering => self.filtering = true,
+            KeyCode::Char(c) if !self.filtering => {
+                let hotkey = |o: &Choice| o.hotkey == Some(c) && !o.disabled;
+                if let Some(index) = self.options.iter().position(hotkey) {
+                    if !self.multi {
+                        return Action::Done(vec![index]);
+                    }
+                    self.selected[index] = !self.selected[index];
+                }
+            }
+            KeyCode::Char(c) => {
+                self.filter.push(c);
+                self.refilter();
+            }
+            _ => {}
+        }
+        Action::Redraw
+    }
+
+    /// Whether the option shown at `position` can be chosen.
+    fn selectable(&self, position: usize) -> bool {
+        self.shown
+            .get(position)
+            .is_some_and(|&i| !self.options[i].disabled)
+    }
+
+    /// Highlight the nearest option that can be chosen from `position`,
+    /// looking down first if `down`, or else up first.
+    fn move_to(&mut self, position: usize, down: bool) {
+        let below = (position..self.shown.len()).find(|&p| self.selectable(p));
+        let above = (0..=position).rev().find(|&p| self.selectable(p));
+        let nearest = match down {
+            true => below.or(above),
+            false => above.or(below),
+        };
+        self.cursor = nearest.unwrap_or(0);
+    }
+
+    /// Show only the options that match the filter, best first. Those
+    /// that can't be chosen are left out once there's a filter.
+    fn refilter(&mut self) {
+        let mut scored: Vec<(i64, usize)> = self
+            .options
+            .iter()
+            .enumerate()
+            .filter_map(|(i, option)| match self.filter.as_str() {
+                "" => Some((0, i)),
+                _ if option.disabled => None,
+                filter => Select::<Choice>::DEFAULT_SCORER(filter, option, &option.label, i)
+                    .map(|score| (score, i)),
+            })
+            .collect();
+        scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+        self.shown = scored.into_iter().map(|(_, i)| i).collect();
+        self.move_to(0, true);
+    }
+
+    fn highlighted(&self) -> Option<&Choice> {
+        match self.selectable(self.cursor) {
+            true => Some(&self.options[self.shown[self.cursor]]),
+            false => None,
+        }
+    }
+}
+
+impl KeyPrompt for Picker<'_> {
+    type Answer = Vec<usize>;
+
+    fn question(&self) -> &str {
+        self.question
+    }
+
+    fn handle(&mut self, key: KeyEvent) -> InquireResult<Action<Vec<usize>>> {
+        Ok(self.press(key))
+    }
+
+    fn draw(&mut self, out: &mut impl Write) -> io::Result<usize> {
+        let config = render_config();
+        let width = rawprompt::width()?;
+
+        // Keep the highlighted option on the page:
+        if self.cursor < self.top {
+            self.top = self.cursor;
+        } else if self.cursor >= self.top + PAGE_SIZE {
+            self.top = self.cursor + 1 - PAGE_SIZE;
+        }
+        self.top = self.top.min(self.shown.len().saturating_sub(PAGE_SIZE));
+
+        queue!(out, cursor::Hide)?;
+        let input = match (self.hotkeys, self.filtering) {
+            (true, true) => format!("/{}", self.filter),
+            _ => self.filter.clone(),
+        };
+        rawprompt::print_question(out, self.question, &input, None, width)?;
+        let mut lines = 1;
+        let page = self.top..(self.top + PAGE_SIZE).min(self.shown.len());
+        for position in page.clone() {
+            let option = &self.options[self.shown[position]];
+            let highlighted = position == self.cursor && !option.disabled;
+            let prefix = match position {
+                _ if highlighted => &config.highlighted_option_prefix,
+                p if p == page.start && p > 0 => &config.scroll_up_prefix,
+                p if p + 1 == page.end && p + 1 < self.shown.len() => &config.scroll_down_prefix,
+                _ => &config.unhighlighted_option_prefix,
+            };
+            let selected = self.selected[self.shown[position]];
+            let checkbox = match (self.multi && !option.disabled, selected) {
+                (false, _) => ("", config.option),
+                (true, true) => styled(&config.selected_checkbox),
+                (true, false) => styled(&config.unselected_checkbox),
+            };
+            let label = format!(
+                "{}{}",
+                option
+                    .hotkey
+                    .map(|k| format!("[{}] ", k))
+                    .unwrap_or_default(),
+                option.label
+            );
+            let label_style = match highlighted {
+                true => config.selected_option.unwrap_or(config.option),
+                false => config.option,
+            };
+            let gap = match checkbox.0 {
+                "" => "",
+                _ => " ",
+            };
+            queue!(out, Print("\r\n"))?;
+            let parts = [
+                styled(prefix),
+                (" ", config.option),
+                checkbox,
+                (gap, config.option),
+                (&label, label_style),
+            ];
+            print(out, &parts, width)?;
+            lines += 1;
+        }
+        if let Some(description) = self.highlighted().and_then(|o| o.description.as_deref()) {
+            queue!(out, Print("\r\n"))?;
+            print(
+                out,
+                &[(&format!("  {}", description), config.placeholder)],
+                width,
+            )?;
+            lines += 1;
+        }
+        let help = match self.deadline {
+            Some(deadline) => {
+                let left = deadline.saturating_duration_since(Instant::now());
+                let seconds = left.as_secs() + u64::from(left.subsec_nanos() > 0);
+                Some(format!("{}s left, press any key to answer", seconds))
+            }
+            None => self.help.map(str::to_string),
+        };
+        if let Some(help) = help {
+            rawprompt::print_help(out, &help, width)?;
+            lines += 1;
+        }
+        Ok(lines - 1)
+    }
+
+    fn show(&self, indexes: &Vec<usize>) -> String {
+        let labels: Vec<&str> = indexes
+            .iter()
+            .map(|&i| self.options[i].label.as_str())
+            .collect();
+        labels.join(", ")
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(picker: &mut Picker, keys: &str) -> Action<Vec<usize>> {
+        let mut action = Action::Redraw;
+        for c in keys.chars() {
+            let code = match c {
+                '\n' => KeyCode::Enter,
+                '\x1b' => KeyCode::Esc,
+                c => KeyCode::Char(c),
+            };
+            action = picker.press(KeyEvent::new(code, KeyModifiers::NONE));
+        }
+        action
+    }
+
+    #[test]
+    fn picks_options_by_hotkey() {
+        let mut drop = Choice::new("Drop", "drop");
+        drop.hotkey = Some('d');
+        let options = [
+            Choice::new("Migrate", "migrate"),
+            drop,
+            Choice::new("Undo", "undo"),
+        ];
+
+        let mut picker = Picker::new("Database", &options, false);
+        assert_eq!(press(&mut picker, "x"), Action::Redraw);
+        assert_eq!(picker.shown, [0, 1, 2]);
+        assert_eq!(press(&mut picker, "d"), Action::Done(vec![1]));
+
+        // Typed after /, keys filter instead:
+        let mut picker = Picker::new("Database", &options, false);
+        assert_eq!(press(&mut picker, "/und"), Action::Redraw);
+        assert_eq!(picker.shown, [2]);
+        assert_eq!(press(&mut picker, "\n"), Action::Done(vec![2]));
+        let mut picker = Picker::new("Database", &options, false);
+        assert_eq!(press(&mut picker, "/d\x1b"), Action::Redraw);
+        assert_eq!(press(&mut picker, "\x1b"), Action::Cancel);
+
+        // Without any hotkeys, typing filters straight away:
+        let options = [
+            Choice::new("Migrate", "migrate"),
+            Choice::new("Drop", "drop"),
+        ];
+        let mut picker = Picker::new("Database", &options, false);
+        assert_eq!(press(&mut picker, "d\n"), Action::Done(vec![1]));
+    }
+
+    #[test]
+    fn skips_options_that_cant_be_chosen() {
+        let mut heading = Choice::new("── Danger ──", "");
+        heading.disabled = true;
+        let mut drop = Choice::new("Drop", "drop");
+        drop.hotkey = Some('d');
+        drop.disabled = true;
+        let mut undo = Choice::new("Undo", "undo");
+        undo.hotkey = Some('u');
+        let options = [heading, Choice::new("Migrate", "migrate"), drop, undo];
+
+        let mut picker = Picker::new("Database", &options, false);
+        assert_eq!(picker.cursor, 1);
+        let down = KeyEvent::new(KeyCode::Down, KeyModifiers::NONE);
+        let up = KeyEvent::new(KeyCode::Up, KeyModifiers::NONE);
+        picker.press(down);
+        assert_eq!(picker.cursor, 3);
+        picker.press(down);
+        assert_eq!(picker.cursor, 1);
+        picker.press(up);
+        assert_eq!(picker.cursor, 3);
+        // Its hotkey does nothing, and filtering leaves it out:
+        assert_eq!(press(&mut picker, "d"), Action::Redraw);
+        assert_eq!(press(&mut picker, "/d"), Action::Redraw);
+        assert_eq!(picker.shown, [3]);
+        let mut picker = Picker::new("Database", &options, true).starting_cursor(0);
+        assert_eq!(picker.cursor, 1);
+        picker.press(KeyEvent::new(KeyCode::Right, KeyModifiers::NONE));
+        assert_eq!(press(&mut picker, "\n"), Action::Done(vec![1, 3]));
+    }
+
+    #[test]
+    fn describes_the_highlighted_option() {
+        let mut prod = Choice::new("Production", "prod");
+        prod.description = Some("the live site".to_string());
+        let options = [Choice::new("Staging", "stg"), prod];
+        assert_eq!(options[1].to_string(), "Production");
+
+        let mut picker = Picker::new("Deploy to", &options, false);
+        assert_eq!(picker.highlighted().unwrap().description, None);
+        press(&mut picker, "Prod");
+        let description = picker.highlighted().unwrap().description.as_deref();
+        assert_eq!(description, Some("the live site"));
+        // The description isn't filtered on:
+        press(&mut picker, " live");
+        assert!(picker.highlighted().is_none());
+    }
+}
@@ -1,89 +1,157 @@
-use bendy::decoding::{Decoder, Object};
-use serde_json::Value as JsonValue;
-use std::collections::HashMap;
+use crate::ask::{self, MenuExit, WizardError};
+use crate::cli::{self, Cli, Failure, Outcome};
+use crate::payload::{Data, Format};
+use bendy::decoding::{Decoder, FromBencode};
+use bendy::value::Value as BencodeObject;
+use clap::{Arg, ArgAction, CommandFactory, Parser};
+use custom_error::custom_error;
+use serde_json::{json, Map, Value as JsonValue};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::io::{self, Read, Write};
-use std::process::{Command, Stdio};
 
 const NAMESPACE: &str = "pod.enigmacurry.script-wizard";
 
-fn read_bencode_message(reader: &mut impl Read) -> Option<HashMap<String, String>> {
-    // Read all available bytes and try to decode a bencode dict
-    let mut buf = Vec::new();
-    let mut byte = [0u8; 1];
+/// How deeply lists and dicts may nest in a message.
+const MAX_DEPTH: usize = 64;
+
+/// The longest byte string accepted in a message.
+const MAX_STRING_LENGTH: usize = 64 * 1024 * 1024;
+
+custom_error! {ReadError
+    Io{source:io::Error} = "Could not read message: {source}",
+    Truncated = "Message ended early",
+    Malformed{message:String} = "Malformed message: {message}",
+}
 
-    // Bencode dicts start with 'd' and end with 'e'
-    // We need to read one complete bencode value
-    if reader.read_exact(&mut byte).is_err() {
-        return None;
+fn malformed(message: impl Into<String>) -> ReadError {
+    ReadError::Malformed {
+        message: message.into(),
     }
-    if byte[0] != b'd' {
-        return None;
+}
+
+/// A message from the client: a dict of any bencode values.
+struct Message(BTreeMap<Cow<'static, [u8]>, BencodeObject<'static>>);
+
+impl Message {
+    fn get(&self, key: &str) -> Option<&BencodeObject<'static>> {
+        self.0.get(key.as_bytes())
     }
-    buf.push(byte[0]);
 
-    // Read until we have a complete dict - track nesting depth
-    let mut depth = 1;
-    while depth > 0 {
-        if reader.read_exact(&mut byte).is_err() {
-            return None;
+    /// The value of `key`, if it is a (UTF-8) byte string.
+    fn str(&self, key: &str) -> Option<&str> {
+        match self.get(key)? {
+            BencodeObject::Bytes(bytes) => std::str::from_utf8(bytes).ok(),
+            _ => None,
         }
-        buf.push(byte[0]);
-        match byte[0] {
-            b'd' | b'l' => depth += 1,
-            b'e' => depth -= 1,
-            b'0'..=b'9' => {
-                // It's a byte string length prefix - read the length then the string
-                let mut len_str = String::new();
-                len_str.push(byte[0] as char);
-                loop {
-                    if reader.read_exact(&mut byte).is_err() {
-                        return None;
-                    }
-                    buf.push(byte[0]);
-                    if byte[0] == b':' {
-                        break;
-                    }
-                    len_str.push(byte[0] as char);
+    }
+}
+
+/// Read one byte, or `None` at the end of the input.
+fn read_byte(reader: &mut impl Read) -> Result<Option<u8>, ReadError> {
+    let mut byte = [0u8; 1];
+    loop {
+        return match reader.read(&mut byte) {
+            Ok(0) => Ok(None),
+            Ok(_) => Ok(Some(byte[0])),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(source) => Err(ReadError::Io { source }),
+        };
+    }
+}
+
+/// Read one byte into `buf`, which must not be the end of the input.
+fn next_byte(reader: &mut impl Read, buf: &mut Vec<u8>) -> Result<u8, ReadError> {
+    let byte = read_byte(reader)?.ok_or(ReadError::Truncated)?;
+    buf.push(byte);
+    Ok(byte)
+}
+
+/// Read the rest of the bencode value starting with `first` into
+/// `buf`, checking its structure as it streams in.
+fn read_value(
+    reader: &mut impl Read,
+    buf: &mut Vec<u8>,
+    first: u8,
+    depth: usize,
+) -> Result<(), ReadError> {
+    match first {
+        b'i' => loop {
+            match next_byte(reader, buf)? {
+                b'e' => return Ok(()),
+                b'-' | b'0'..=b'9' => {}
+                b => {
+                    return Err(malformed(format!(
+                        "unexpected {:?} in an integer",
+                        b as char
+                    )))
                 }
-                let len: usize = len_str.parse().ok()?;
-                let mut string_buf = vec![0u8; len];
-                if reader.read_exact(&mut string_buf).is_err() {
-                    return None;
+            }
+        },
+        b'l' | b'd' => {
+            if depth >= MAX_DEPTH {
+                return Err(malformed("nested too deeply"));
+            }
+            loop {
+                match next_byte(reader, buf)? {
+                    b'e' => return Ok(()),
+                    b => read_value(reader, buf, b, depth + 1)?,
                 }
-                buf.extend_from_slice(&string_buf);
             }
-            b'i' => {
-                // Integer - read until 'e'
-                loop {
-                    if reader.read_exact(&mut byte).is_err() {
-                        return None;
+        }
+        b'0'..=b'9' => {
+            let mut len = usize::from(first - b'0');
+            loop {
+                match next_byte(reader, buf)? {
+                    b':' => break,
+                    b @ b'0'..=b'9' => {
+                        len = len
+                            .checked_mul(10)
+                            .and_then(|l| l.checked_add(usize::from(b - b'0')))
+                            .filter(|&l| l <= MAX_STRING_LENGTH)
+                            .ok_or_else(|| malformed("string too long"))?;
                     }
-                    buf.push(byte[0]);
-                    if byte[0] == b'e' {
-                        break;
+                    b => {
+                        return Err(malformed(format!(
+                            "unexpected {:?} in a string length",
+                            b as char
+                        )))
                     }
                 }
             }
-            _ => {}
+            let start = buf.len();
+            buf.resize(start + len, 0);
+            reader
+                .read_exact(&mut buf[start..])
+                .map_err(|e| match e.kind() {
+                    io::ErrorKind::UnexpectedEof => ReadError::Truncated,
+                    _ => ReadError::Io { source: e },
+                })
         }
+        b => Err(malformed(format!("unexpected {:?}", b as char))),
     }
+}
 
-    // Now decode the complete message
-    let mut decoder = Decoder::new(&buf);
-    let dict = match decoder.next_object().ok()?? {
-        Object::Dict(mut d) => {
-            let mut map = HashMap::new();
-            while let Ok(Some((key_bytes, value_obj))) = d.next_pair() {
-                let key = String::from_utf8_lossy(key_bytes).to_string();
-                if let Object::Bytes(val) = value_obj {
-                    map.insert(key, String::from_utf8_lossy(val).to_string());
-                }
-            }
-            map
-        }
-        _ => return None,
+/// Read the next message, or `None` once the client closes the stream.
+fn read_bencode_message(reader: &mut impl Read) -> Result<Option<Message>, ReadError> {
+    let Some(first) = read_byte(reader)? else {
+        return Ok(None);
     };
-    Some(dict)
+    let mut buf = vec![first];
+    read_value(reader, &mut buf, first, 0)?;
+    let mut decoder = Decoder::new(&buf).with_max_depth(MAX_DEPTH + 1);
+    let object = decoder
+        .next_object()
+        .map_err(|e| malformed(e.to_string()))?
+        .ok_or(ReadError::Truncated)?;
+    match BencodeObject::decode_bencode_object(object).map_err(|e| malformed(e.to_string()))? {
+        BencodeObject::Dict(dict) => Ok(Some(Message(
+            dict.into_iter()
+                .map(|(k, v)| (Cow::Owned(k.into_owned()), v.into_owned()))
+                .collect(),
+        ))),
+        _ => Err(malformed("expected a dict")),
+    }
 }
 
 /// A bencode value that can be a string, list, or dict.
@@ -124,121 +192,167 @@ fn encode_bencode(val: &BencodeValue) -> Vec<u8> {
     }
 }
 
-fn write_describe_response(writer: &mut impl Write) {
-    // Vars with code wrappers for idiomatic keyword-arg calling convention.
-    // Internal pod functions use positional + map args; code wraps them.
-    let ns_sym = NAMESPACE;
+/// The prompt kinds the pod offers: every subcommand but `pod` itself
+/// (and clap's `help`), straight from the CLI's definition so the two
+/// can't drift apart.
+fn kinds() -> Vec<clap::Command> {
+    let mut cli = Cli::command();
+    // Building it gives each subcommand the global options too:
+    cli.build();
+    cli.get_subcommands()
+        .filter(|c| !matches!(c.get_name(), "pod" | "help"))
+        .cloned()
+        .collect()
+}
+
+/// The arguments the var for `kind` takes: its own and the global
+/// options, but not `--help`.
+fn arguments(kind: &clap::Command) -> impl Iterator<Item = &Arg> {
+    kind.get_arguments()
+        .filter(|a| !matches!(a.get_action(), ArgAction::Help | ArgAction::Version))
+}
+
+/// Whether `arg` is a positional parameter of the var for `kind`: the
+/// required and list positionals, and any before them. Other optional
+/// positionals, like the `ask` default, are keyword options instead.
+fn is_param(kind: &clap::Command, arg: &Arg) -> bool {
+    let positionals: Vec<&Arg> = kind.get_positionals().collect();
+    let last = positionals
+        .iter()
+        .rposition(|a| a.is_required_set() || matches!(a.get_action(), ArgAction::Append));
+    let index = positionals.iter().position(|a| a.get_id() == arg.get_id());
+    matches!((index, last), (Some(index), Some(last)) if index <= last)
+}
 
-    struct VarDef {
-        name: &'static str,
-        meta: &'static str,
-        code: Option<String>,
+/// The keyword naming `arg` in the pod, eg. `allow-blank`.
+fn keyword(arg: &Arg) -> String {
+    arg.get_long()
+        .map(String::from)
+        .unwrap_or_else(|| arg.get_id().as_str().replace('_', "-"))
+}
+
+/// The `:doc` and `:arglists` metadata of the var for `kind`.
+fn var_meta(kind: &clap::Command) -> String {
+    let params: Vec<String> = arguments(kind)
+        .filter(|a| is_param(kind, a))
+        .map(keyword)
+        .collect();
+    let options: Vec<&Arg> = arguments(kind).filter(|a| !is_param(kind, a)).collect();
+    let mut doc = kind.get_about().map(|a| a.to_string()).unwrap_or_default();
+    doc.push_str("\n  Options:");
+    for option in &options {
+        doc.push_str(&format!("\n    :{}", keyword(option)));
+        if let Some(help) = option.get_help() {
+            doc.push_str(&format!(" - {}", help));
+        }
     }
+    doc.push_str(match kind.get_name() {
+        "menu" => {
+            "\n    :on-cancel - Return this when the menu is canceled (nil by default)\
+             \n  Entries may also be [label handler] pairs of Clojure functions; \
+             a nil handler exits the menu."
+        }
+        _ => {
+            "\n    :on-cancel - Return this when the prompt is canceled, instead of \
+             throwing ex-info with {:type :script-wizard/canceled}"
+        }
+    });
+    let keys: Vec<String> = options
+        .iter()
+        .map(|o| keyword(o))
+        .chain(["on-cancel".to_string()])
+        .collect();
+    format!(
+        "{{:doc {} :arglists ([{}& {{:keys [{}]}}])}}",
+        JsonValue::from(doc),
+        params.iter().map(|p| format!("{} ", p)).collect::<String>(),
+        keys.join(" ")
+    )
+}
 
-    let vars = vec![
-        VarDef {
-            name: "ask*",
-            meta: "",
-            code: None,
-        },
-        VarDef {
-            name: "ask",
-            meta: "{:doc \"Ask a free-text question. Returns the response string.\n  Options: :default, :allow-blank, :suggestions\" :arglists ([question & {:keys [default allow-blank suggestions]}])}",
-            code: Some(format!(
-                "(defn ask [question & {{:keys [default allow-blank suggestions]}}] (try ({ns_sym}/ask* question {{\"default\" default \"allow-blank\" allow-blank \"suggestions\" suggestions}}) (catch Exception e (if (= \"canceled\" (ex-message e)) (System/exit 1) (throw e)))))"
-            )),
-        },
-        VarDef {
-            name: "confirm*",
-            meta: "",
-            code: None,
-        },
-        VarDef {
-            name: "confirm",
-            meta: "{:doc \"Ask a yes/no question. Returns true or false.\n  Options: :default\" :arglists ([question & {:keys [default]}])}",
-            code: Some(format!(
-                "(defn confirm [question & {{:keys [default]}}] (try ({ns_sym}/confirm* question {{\"default\" (when default (name default))}}) (catch Exception e (if (= \"canceled\" (ex-message e)) (System/exit 1) (throw e)))))"
-            )),
-        },
-        VarDef {
-            name: "choose*",
-            meta: "",
-            code: None,
-        },
-        VarDef {
-            name: "choose",
-            meta: "{:doc \"Choose one item from a list. Returns the chosen string.\n  Options: :default\" :arglists ([question options & {:keys [default]}])}",
-            code: Some(format!(
-                "(defn choose [question options & {{:keys [default]}}] (try ({ns_sym}/choose* question options {{\"default\" default}}) (catch Exception e (if (= \"canceled\" (ex-message e)) (System/exit 1) (throw e)))))"
-            )),
-        },
-        VarDef {
-            name: "select*",
-            meta: "",
-            code: None,
-        },
-        VarDef {
-            name: "select",
-            meta: "{:doc \"Select multiple items from a list. Returns a vector of chosen strings.\n  Options: :default\" :arglists ([question options & {:keys [default]}])}",
-            code: Some(format!(
-                "(defn select [question options & {{:keys [default]}}] (try ({ns_sym}/select* question options {{\"default\" default}}) (catch Exception e (if (= \"canceled\" (ex-message e)) (System/exit 1) (throw e)))))"
-            )),
-        },
-        VarDef {
-            name: "date*",
-            meta: "",
-            code: None,
-        },
-        VarDef {
-            name: "date",
-            meta: "{:doc \"Pick a date interactively. Returns a date string.\n  Options: :default, :format, :min-date, :max-date, :starting-date, :week-start, :help-message\" :arglists ([question & {:keys [default format min-date max-date starting-date week-start help-message]}])}",
-            code: Some(format!(
-                "(defn date [question & {{:keys [default format min-date max-date starting-date week-start help-message]}}] (try ({ns_sym}/date* question {{\"default\" default \"format\" format \"min-date\" min-date \"max-date\" max-date \"starting-date\" starting-date \"week-start\" week-start \"help-message\" help-message}}) (catch Exception e (if (= \"canceled\" (ex-message e)) (System/exit 1) (throw e)))))"
-            )),
-        },
-        VarDef {
-            name: "editor*",
-            meta: "",
-            code: None,
-        },
-        VarDef {
-            name: "editor",
-            meta: "{:doc \"Open a full text editor for input. Returns the entered text.\n  Options: :default, :help-message, :file-extension\" :arglists ([message & {:keys [default help-message file-extension]}])}",
-            code: Some(format!(
-                "(defn editor [message & {{:keys [default help-message file-extension]}}] (try ({ns_sym}/editor* message {{\"default\" default \"help-message\" help-message \"file-extension\" file-extension}}) (catch Exception e (if (= \"canceled\" (ex-message e)) (System/exit 1) (throw e)))))"
-            )),
-        },
-        VarDef {
-            name: "menu",
-            meta: "{:doc \"Interactive menu that loops until quit. Entries are [label handler] pairs.\n  A nil handler exits the menu.\n  Options: :once, :default\" :arglists ([heading entries & {:keys [once default]}])}",
-            code: Some(format!(concat!(
-                "(defn menu [heading entries & {{:keys [once default]}}] ",
+/// Calls `f` with the options but `:on-cancel`. When the prompt is
+/// canceled, returns the `:on-cancel` value if there is one, or else
+/// throws the pod's error with its `:type` as a keyword (the JSON
+/// format can only send it as a string).
+const WITH_CANCEL: &str = concat!(
+    "(defn- with-cancel [opts f] ",
+    "(try (f (dissoc opts :on-cancel)) ",
+    "(catch clojure.lang.ExceptionInfo e ",
+    "(let [data (ex-data e) ",
+    "data (assoc data :type (keyword (or (:type data) (get data \"type\"))))] ",
+    "(cond (not= :script-wizard/canceled (:type data)) (throw e) ",
+    "(contains? opts :on-cancel) (:on-cancel opts) ",
+    ":else (throw (ex-info (ex-message e) data e)))))))"
+);
+
+/// The wrapper that calls the `*` var for `kind` with keyword options.
+fn var_code(kind: &clap::Command) -> String {
+    let name = kind.get_name();
+    let params: String = arguments(kind)
+        .filter(|a| is_param(kind, a))
+        .map(|a| format!("{} ", keyword(a)))
+        .collect();
+    if name == "menu" {
+        // Canceling is the usual way out of a menu, so it returns nil
+        // unless told otherwise. Clojure handlers can't cross into the
+        // pod, so the menu loop for those runs in the client:
+        return format!(
+            concat!(
+                "(defn menu [{params}& {{:as opts}}] ",
+                "(let [opts (merge {{:on-cancel nil}} opts)] ",
+                "(if (every? string? entries) ",
+                "(with-cancel opts #({ns}/menu* {params}%)) ",
                 "(let [labels (mapv first entries)] ",
-                "(loop [dflt default] ",
-                "(let [choice (try ({ns_sym}/choose* heading labels {{\"default\" dflt}}) (catch Exception e (if (= \"canceled\" (ex-message e)) nil (throw e))))] ",
-                "(if (nil? choice) nil ",
-                "(let [handler (second (first (filter #(= (first %) choice) entries)))] ",
-                "(if (nil? handler) nil ",
-                "(do (handler) ",
-                "(if once nil (recur choice))))))))))"
-            ), ns_sym = ns_sym)),
-        },
-    ];
+                "(loop [dflt (:default opts)] ",
+                "(let [choice (with-cancel {{:on-cancel ::canceled}} ",
+                "(fn [_] ({ns}/choose* heading labels {{:default dflt}})))] ",
+                "(if (= ::canceled choice) (:on-cancel opts) ",
+                "(when-let [handler (second (first (filter #(= (first %) choice) entries)))] ",
+                "(handler) ",
+                "(when-not (:once opts) (recur choice))))))))))"
+            ),
+            ns = NAMESPACE,
+            params = params,
+        );
+    }
+    format!(
+        "(defn {name} [{params}& {{:as opts}}] (with-cancel opts #({ns}/{name}* {params}%)))",
+        name = name,
+        ns = NAMESPACE,
+        params = params,
+    )
+}
 
-    let var_list: Vec<BencodeValue> = vars
-        .into_iter()
-        .map(|v| {
-            let mut fields = vec![
-                ("name".to_string(), BencodeValue::Str(v.name.to_string())),
-            ];
-            if !v.meta.is_empty() {
-                fields.push(("meta".to_string(), BencodeValue::Str(v.meta.to_string())));
-            }
-            if let Some(code) = v.code {
-                fields.push(("code".to_string(), BencodeValue::Str(code)));
-            }
-            BencodeValue::Dict(fields)
-        })
+fn write_describe_response(writer: &mut impl Write, format: Format) -> io::Result<()> {
+    // Each kind has a `*` var taking positional parameters and a map of
+    // options, and a wrapper taking keyword options.
+    let helper = BencodeValue::Dict(vec![
+        (
+            "name".to_string(),
+            BencodeValue::Str("with-cancel".to_string()),
+        ),
+        (
+            "code".to_string(),
+            BencodeValue::Str(WITH_CANCEL.to_string()),
+        ),
+    ]);
+    let var_list: Vec<BencodeValue> = std::iter::once(helper)
+        .chain(kinds().iter().flat_map(|kind| {
+            [
+                BencodeValue::Dict(vec![(
+                    "name".to_string(),
+                    BencodeValue::Str(format!("{}*", kind.get_name())),
+                )]),
+                BencodeValue::Dict(vec![
+                    (
+                        "name".to_string(),
+                        BencodeValue::Str(kind.get_name().to_string()),
+                    ),
+                    ("meta".to_string(), BencodeValue::Str(var_meta(kind))),
+                    ("code".to_string(), BencodeValue::Str(var_code(kind))),
+                ]),
+            ]
+        }))
         .collect();
 
     let ns = BencodeValue::Dict(vec![
@@ -246,22 +360,22 @@ fn write_describe_response(writer: &mut impl Write) {
         ("vars".to_string(), BencodeValue::List(var_list)),
     ]);
 
-    let ops = BencodeValue::Dict(vec![(
-        "shutdown".to_string(),
-        BencodeValue::Dict(vec![]),
-    )]);
+    let ops = BencodeValue::Dict(vec![("shutdown".to_string(), BencodeValue::Dict(vec![]))]);
 
     let response = BencodeValue::Dict(vec![
-        ("format".to_string(), BencodeValue::Str("json".to_string())),
+        (
+            "format".to_string(),
+            BencodeValue::Str(format.name().to_string()),
+        ),
         ("namespaces".to_string(), BencodeValue::List(vec![ns])),
         ("ops".to_string(), ops),
     ]);
 
-    writer.write_all(&encode_bencode(&response)).unwrap();
-    writer.flush().unwrap();
+    writer.write_all(&encode_bencode(&response))?;
+    writer.flush()
 }
 
-fn write_invoke_response(writer: &mut impl Write, id: &str, value: &str) {
+fn write_invoke_response(writer: &mut impl Write, id: &str, value: &str) -> io::Result<()> {
     let response = BencodeValue::Dict(vec![
         ("id".to_string(), BencodeValue::Str(id.to_string())),
         (
@@ -270,17 +384,41 @@ fn write_invoke_response(writer: &mut impl Write, id: &str, value: &str) {
         ),
         ("value".to_string(), BencodeValue::Str(value.to_string())),
     ]);
-    writer.write_all(&encode_bencode(&response)).unwrap();
-    writer.flush().unwrap();
+    writer.write_all(&encode_bencode(&response))?;
+    writer.flush()
 }
 
-fn write_invoke_error(writer: &mut impl Write, id: &str, message: &str) {
-    let response = BencodeValue::Dict(vec![
+/// The `ex-data` for an error: `fields` with a `:type` keyword that
+/// scripts can dispatch on, eg. `:script-wizard/canceled`.
+fn error_data(kind: &str, fields: JsonValue) -> Data {
+    let mut entries = vec![(
+        Data::Keyword("type".to_string()),
+        Data::Keyword(format!("script-wizard/{}", kind)),
+    )];
+    if let Data::Map(fields) = Data::from(fields) {
+        entries.extend(fields);
+    }
+    Data::Map(entries)
+}
+
+/// Report an error to the client. The `id` is unknown when the
+/// request itself couldn't be read.
+fn write_invoke_error(
+    writer: &mut impl Write,
+    format: Format,
+    id: Option<&str>,
+    message: &str,
+    data: &Data,
+) -> io::Result<()> {
+    let mut fields = vec![
+        (
+            "ex-data".to_string(),
+            BencodeValue::Str(format.encode(data)),
+        ),
         (
             "ex-message".to_string(),
             BencodeValue::Str(message.to_string()),
         ),
-        ("id".to_string(), BencodeValue::Str(id.to_string())),
         (
             "status".to_string(),
             BencodeValue::List(vec![
@@ -288,299 +426,722 @@ fn write_invoke_error(writer: &mut impl Write, id: &str, message: &str) {
                 BencodeValue::Str("error".to_string()),
             ]),
         ),
-    ]);
-    writer.write_all(&encode_bencode(&response)).unwrap();
-    writer.flush().unwrap();
+    ];
+    if let Some(id) = id {
+        fields.push(("id".to_string(), BencodeValue::Str(id.to_string())));
+    }
+    writer.write_all(&encode_bencode(&BencodeValue::Dict(fields)))?;
+    writer.flush()
 }
 
-fn invoke_script_wizard(args: &[String]) -> Result<String, String> {
-    let exe = std::env::current_exe().unwrap_or_else(|_| "script-wizard".into());
-
-    let mut cmd = Command::new(&exe);
-    cmd.args(args).stdout(Stdio::piped());
-
-    #[cfg(unix)]
-    let saved_stdin = unsafe {
-        use std::os::unix::io::FromRawFd;
-        // Open /dev/tty and dup it so we pass a copy to the child
-        // while the parent keeps a fd open (preventing input buffer gaps).
-        // O_CLOEXEC so unrelated child spawns don't inherit this fd.
-        let tty_fd = libc::open(
-            b"/dev/tty\0".as_ptr() as *const _,
-            libc::O_RDWR | libc::O_CLOEXEC,
-        );
-        if tty_fd < 0 {
-            return Err(format!("Cannot open /dev/tty: {}", std::io::Error::last_os_error()));
-        }
-        libc::tcflush(tty_fd, libc::TCIFLUSH);
-        // Dup for child stdin and stderr; parent keeps tty_fd open.
-        let child_in = libc::dup(tty_fd);
-        if child_in < 0 {
-            let err = std::io::Error::last_os_error();
-            libc::close(tty_fd);
-            return Err(format!("dup /dev/tty for stdin: {}", err));
-        }
-        let child_err = libc::dup(tty_fd);
-        if child_err < 0 {
-            let err = std::io::Error::last_os_error();
-            libc::close(child_in);
-            libc::close(tty_fd);
-            return Err(format!("dup /dev/tty for stderr: {}", err));
+/// Why an invocation failed.
+enum InvokeError {
+    /// The arguments don't fit the var
+    BadArgs(String),
+    /// The `prompt` failed, eg. the user canceled it, and `code` is
+    /// the exit code the command line would use
+    Prompt {
+        prompt: String,
+        error: WizardError,
+        code: u8,
+    },
+}
+
+impl InvokeError {
+    /// The error for the `prompt` subcommand failing.
+    fn failed(prompt: &str, failure: Failure) -> Self {
+        match failure {
+            Failure::Usage(message) => InvokeError::BadArgs(message),
+            Failure::Prompt { error, cancel_code } => InvokeError::Prompt {
+                prompt: prompt.to_string(),
+                code: ask::exit_code(&error, cancel_code),
+                error,
+            },
         }
-        cmd.stdin(Stdio::from(std::os::unix::io::OwnedFd::from_raw_fd(child_in)))
-           .stderr(Stdio::from(std::os::unix::io::OwnedFd::from_raw_fd(child_err)));
-        tty_fd
-    };
+    }
 
-    #[cfg(windows)]
-    {
-        cmd.stdin(Stdio::from(
-            std::fs::OpenOptions::new()
-                .read(true)
-                .open("CONIN$")
-                .map_err(|e| format!("Cannot open CONIN$: {}", e))?,
-        ))
-        .stderr(Stdio::from(
-            std::fs::OpenOptions::new()
-                .write(true)
-                .open("CONOUT$")
-                .map_err(|e| format!("Cannot open CONOUT$: {}", e))?,
-        ));
+    /// The `ex-message` and `ex-data` to throw in the client.
+    fn describe(&self) -> (String, Data) {
+        match self {
+            InvokeError::BadArgs(message) => (
+                message.clone(),
+                error_data("bad-args", json!({"error": "bad-args"})),
+            ),
+            // Ctrl-C cancels the prompt, same as ESC:
+            InvokeError::Prompt {
+                prompt,
+                error: e @ (WizardError::Canceled | WizardError::Interrupted),
+                code,
+            } => (
+                "canceled".to_string(),
+                error_data(
+                    "canceled",
+                    json!({"prompt": prompt, "error": ask::error_kind(e), "code": code}),
+                ),
+            ),
+            InvokeError::Prompt {
+                prompt,
+                error: e,
+                code,
+            } => (
+                e.to_string(),
+                error_data(
+                    "error",
+                    json!({"prompt": prompt, "error": ask::error_kind(e), "code": code}),
+                ),
+            ),
+        }
     }
+}
 
-    let output = cmd
-        .output()
-        .map_err(|e| format!("Failed to spawn: {}", e));
+/// An argument value as command line text.
+fn arg_text(value: &JsonValue) -> String {
+    match value {
+        JsonValue::String(s) => s.clone(),
+        v => v.to_string(),
+    }
+}
 
-    // Close the parent's tty fd (child fds are already closed)
-    #[cfg(unix)]
-    unsafe {
-        libc::close(saved_stdin);
+/// The command line for invoking `kind` with the pod `args`: its
+/// positional parameters followed by a map of keyword options.
+fn command_line(kind: &clap::Command, args: &[JsonValue]) -> Result<Vec<String>, InvokeError> {
+    let name = kind.get_name();
+    let params: Vec<&Arg> = arguments(kind).filter(|a| is_param(kind, a)).collect();
+    let options = match args.get(params.len()) {
+        None | Some(JsonValue::Null) => Map::new(),
+        Some(JsonValue::Object(options)) => options.clone(),
+        Some(v) => {
+            return Err(InvokeError::BadArgs(format!(
+                "{}: expected a map of options, not {}",
+                name, v
+            )))
+        }
+    };
+    if let Some(unknown) = options
+        .keys()
+        .find(|k| !arguments(kind).any(|a| !is_param(kind, a) && keyword(a) == **k))
+    {
+        return Err(InvokeError::BadArgs(format!(
+            "{}: unknown option :{}",
+            name, unknown
+        )));
     }
 
-    let output = output?;
-    let code = output.status.code().unwrap_or(1);
-    if code == 0 {
-        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
-    } else if code == 2 {
-        Err("canceled".to_string())
-    } else {
-        Err(format!("script-wizard exited with code {}", code))
+    let mut argv = vec!["script-wizard".to_string(), name.to_string()];
+    let mut positionals = vec![];
+    for arg in arguments(kind) {
+        let value = match is_param(kind, arg) {
+            true => params
+                .iter()
+                .position(|p| p.get_id() == arg.get_id())
+                .and_then(|index| args.get(index)),
+            false => options.get(&keyword(arg)),
+        };
+        let value = match value {
+            None | Some(JsonValue::Null) if arg.is_required_set() => {
+                return Err(InvokeError::BadArgs(format!(
+                    "{} requires a {}",
+                    name,
+                    keyword(arg)
+                )))
+            }
+            None | Some(JsonValue::Null) => continue,
+            Some(value) => value,
+        };
+        if arg.is_positional() {
+            match value {
+                JsonValue::Array(items) => positionals.extend(items.iter().map(arg_text)),
+                value => positionals.push(arg_text(value)),
+            }
+        } else if matches!(arg.get_action(), ArgAction::SetTrue) {
+            if value != &JsonValue::Bool(false) {
+                argv.push(format!("--{}", keyword(arg)));
+            }
+        } else {
+            argv.push(format!("--{}={}", keyword(arg), arg_text(value)));
+        }
     }
+    argv.push("--".to_string());
+    argv.extend(positionals);
+    Ok(argv)
 }
 
-fn handle_invoke(var: &str, args_json: &str) -> Result<String, String> {
-    let args: Vec<JsonValue> =
-        serde_json::from_str(args_json).map_err(|e| format!("Bad args JSON: {}", e))?;
+fn handle_invoke(var: &str, args: &str, format: Format) -> Result<JsonValue, InvokeError> {
+    let args = match format.decode(args) {
+        Ok(JsonValue::Array(args)) => args,
+        Ok(_) => {
+            return Err(InvokeError::BadArgs(
+                "Bad args: expected a list".to_string(),
+            ))
+        }
+        Err(e) => {
+            return Err(InvokeError::BadArgs(format!(
+                "Bad args {}: {}",
+                format.name(),
+                e
+            )))
+        }
+    };
 
     let fn_name = var.strip_prefix(&format!("{}/", NAMESPACE)).unwrap_or(var);
+    let kind = fn_name
+        .strip_suffix('*')
+        .and_then(|name| kinds().into_iter().find(|k| k.get_name() == name))
+        .ok_or_else(|| InvokeError::BadArgs(format!("Unknown var: {}", var)))?;
 
-    let cmd_args = match fn_name {
-        "ask*" => build_ask_args(&args)?,
-        "confirm*" => build_confirm_args(&args)?,
-        "choose*" => build_choose_args(&args)?,
-        "select*" => build_select_args(&args)?,
-        "date*" => build_date_args(&args)?,
-        "editor*" => build_editor_args(&args)?,
-        _ => return Err(format!("Unknown var: {}", var)),
+    // Parse the same way the command line is, so both accept exactly
+    // the same options:
+    let argv = command_line(&kind, &args)?;
+    let cli = match Cli::try_parse_from(argv) {
+        Ok(cli) => cli,
+        Err(e) => {
+            let message = e.to_string();
+            let message = message.lines().next().unwrap_or_default();
+            return Err(InvokeError::BadArgs(
+                message.trim_start_matches("error: ").to_string(),
+            ));
+        }
     };
+    let Some(command) = &cli.command else {
+        return Err(InvokeError::BadArgs(format!("Unknown var: {}", var)));
+    };
+    // The exit code in a failure depends on the global options too, so
+    // it's worked out while they apply:
+    let run = || cli::run_command(command).map_err(|f| InvokeError::failed(kind.get_name(), f));
+    let outcome = cli
+        .with_globals(run)
+        .map_err(|e| InvokeError::BadArgs(e.to_string()))??;
+    Ok(match outcome {
+        Outcome::Answer { value, json: true } => JsonValue::String(value.to_string()),
+        Outcome::Answer { value, json: false } => value,
+        Outcome::Confirmed(yes) => JsonValue::Bool(yes),
+        Outcome::Text(text) => JsonValue::String(text),
+        Outcome::Menu(MenuExit::Done) => json!("done"),
+        Outcome::Menu(MenuExit::Quit) => json!("quit"),
+    })
+}
 
-    match invoke_script_wizard(&cmd_args) {
-        Ok(result) => match fn_name {
-            "confirm*" => Ok("true".to_string()),
-            "select*" => {
-                let lines: Vec<&str> = result.lines().collect();
-                serde_json::to_string(&lines).map_err(|e| e.to_string())
+/// Move the pod protocol off stdin and stdout onto private file
+/// descriptors, and point stdin, stdout and stderr at the controlling
+/// terminal instead. That way the prompts (and any editor they launch)
+/// use the terminal without mixing into the protocol.
+#[cfg(unix)]
+fn take_protocol_channel() -> io::Result<(Box<dyn Read>, Box<dyn Write>)> {
+    use std::os::unix::io::FromRawFd;
+    unsafe {
+        let input = libc::fcntl(libc::STDIN_FILENO, libc::F_DUPFD_CLOEXEC, 3);
+        if input < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let output = libc::fcntl(libc::STDOUT_FILENO, libc::F_DUPFD_CLOEXEC, 3);
+        if output < 0 {
+            let err = io::Error::last_os_error();
+            libc::close(input);
+            return Err(err);
+        }
+        let reader = io::BufReader::new(std::fs::File::from_raw_fd(input));
+        let writer = std::fs::File::from_raw_fd(output);
+        // Without a terminal, prompts fail (unless they have canned
+        // answers), but the protocol still works:
+        let tty_fd = libc::open(c"/dev/tty".as_ptr(), libc::O_RDWR | libc::O_CLOEXEC);
+        if tty_fd >= 0 {
+            for fd in [libc::STDIN_FILENO, libc::STDOUT_FILENO, libc::STDERR_FILENO] {
+                libc::dup2(tty_fd, fd);
             }
-            _ => serde_json::to_string(&result).map_err(|e| e.to_string()),
-        },
-        Err(e) if e == "canceled" => Err(e),
-        Err(_) if fn_name == "confirm*" => Ok("false".to_string()),
-        Err(e) => Err(e),
+            libc::close(tty_fd);
+        }
+        Ok((Box::new(reader), Box::new(writer)))
     }
 }
 
-fn build_ask_args(args: &[JsonValue]) -> Result<Vec<String>, String> {
-    let question = args
-        .first()
-        .and_then(|v| v.as_str())
-        .ok_or("ask requires a question string")?;
-    let mut cmd = vec!["ask".to_string(), question.to_string()];
+#[cfg(not(unix))]
+fn take_protocol_channel() -> io::Result<(Box<dyn Read>, Box<dyn Write>)> {
+    Ok((
+        Box::new(io::BufReader::new(io::stdin())),
+        Box::new(io::stdout()),
+    ))
+}
 
-    if let Some(opts) = args.get(1).and_then(|v| v.as_object()) {
-        if let Some(default) = opts.get("default").and_then(|v| v.as_str()) {
-            cmd.push(default.to_string());
-        }
-        if opts
-            .get("allow-blank")
-            .and_then(|v| v.as_bool())
-            .unwrap_or(false)
-        {
-            cmd.push("--allow-blank".to_string());
-        }
-        if let Some(suggestions) = opts.get("suggestions").and_then(|v| v.as_array()) {
-            cmd.push("--suggestions".to_string());
-            cmd.push(serde_json::to_string(suggestions).unwrap());
+pub fn run_pod(format: Format) {
+    let (mut reader, mut writer) = match take_protocol_channel() {
+        Ok(channel) => channel,
+        Err(e) => {
+            eprintln!("Cannot set up the pod protocol: {}", e);
+            return;
         }
+    };
+    if let Err(e) = serve(&mut reader, &mut writer, format) {
+        eprintln!("Cannot write to the pod client: {}", e);
     }
-    cmd.extend(["--cancel-code".to_string(), "2".to_string()]);
-    Ok(cmd)
 }
 
-fn build_confirm_args(args: &[JsonValue]) -> Result<Vec<String>, String> {
-    let question = args
-        .first()
-        .and_then(|v| v.as_str())
-        .ok_or("confirm requires a question string")?;
-    let mut cmd = vec!["confirm".to_string(), question.to_string()];
+/// Answer requests until the client shuts the pod down, the input
+/// ends, or a response can't be written.
+fn serve(reader: &mut impl Read, writer: &mut impl Write, format: Format) -> io::Result<()> {
+    loop {
+        let msg = match read_bencode_message(reader) {
+            Ok(Some(msg)) => msg,
+            Ok(None) => break,
+            Err(e) => {
+                let data = error_data("malformed-message", json!({"error": "malformed-message"}));
+                write_invoke_error(writer, format, None, &e.to_string(), &data)?;
+                match e {
+                    // The rest of the stream may still make sense:
+                    ReadError::Malformed { .. } => continue,
+                    ReadError::Truncated | ReadError::Io { .. } => break,
+                }
+            }
+        };
+        let op = msg.str("op").unwrap_or("");
 
-    if let Some(opts) = args.get(1).and_then(|v| v.as_object()) {
-        if let Some(default) = opts.get("default").and_then(|v| v.as_str()) {
-            cmd.push(default.to_string());
+        match op {
+            "describe" => {
+                write_describe_response(writer, format)?;
+            }
+            "invoke" => {
+                let id = msg.str("id").unwrap_or("");
+                let var = msg.str("var").unwrap_or("");
+                let args = msg.str("args").unwrap_or("[]");
+
+                // Drop any keys typed before the prompt is shown:
+                #[cfg(unix)]
+                unsafe {
+                    libc::tcflush(libc::STDIN_FILENO, libc::TCIFLUSH);
+                }
+                match handle_invoke(var, args, format) {
+                    Ok(value) => {
+                        write_invoke_response(writer, id, &format.encode(&Data::from(value)))?;
+                    }
+                    Err(e) => {
+                        let (message, data) = e.describe();
+                        write_invoke_error(writer, format, Some(id), &message, &data)?;
+                    }
+                }
+            }
+            "shutdown" => {
+                break;
+            }
+            _ => {}
         }
     }
-    cmd.extend(["--cancel-code".to_string(), "2".to_string()]);
-    Ok(cmd)
+    Ok(())
 }
 
-fn build_choose_args(args: &[JsonValue]) -> Result<Vec<String>, String> {
-    let question = args
-        .first()
-        .and_then(|v| v.as_str())
-        .ok_or("choose requires a question string")?;
-    let options = args
-        .get(1)
-        .and_then(|v| v.as_array())
-        .ok_or("choose requires an options array")?;
-
-    let mut cmd = vec!["choose".to_string(), question.to_string()];
-    for opt in options {
-        if let Some(s) = opt.as_str() {
-            cmd.push(s.to_string());
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{with_backend, ScriptedBackend};
+    use std::rc::Rc;
+
+    /// A session as babashka writes it: `load-pod` describes the pod,
+    /// the script makes one call, and bb shuts the pod down on exit.
+    const BABASHKA_SESSION: &[u8] = concat!(
+        "d2:id36:8f0d2d2c-9f7e-4b8e-9d0f-6d1f3e1c2a0b2:op8:describee",
+        "d4:args42:[\"What is your name?\",{\"default\":\"World\"}]",
+        "2:id36:1c9d8a42-5e3b-4c1f-a7d2-0e8b6f4a3c912:op6:invoke",
+        "3:var34:pod.enigmacurry.script-wizard/ask*e",
+        "d2:id36:6e2a7b10-3d4c-4f5e-8a9b-1c2d3e4f5a6b2:op8:shutdowne",
+    )
+    .as_bytes();
+
+    fn read_all(mut input: &[u8]) -> Vec<Result<Message, ReadError>> {
+        let mut messages = vec![];
+        loop {
+            match read_bencode_message(&mut input) {
+                Ok(Some(msg)) => messages.push(Ok(msg)),
+                Ok(None) => return messages,
+                Err(e @ ReadError::Malformed { .. }) => messages.push(Err(e)),
+                Err(e) => {
+                    messages.push(Err(e));
+                    return messages;
+                }
+            }
         }
     }
 
-    if let Some(opts) = args.get(2).and_then(|v| v.as_object()) {
-        if let Some(default) = opts.get("default").and_then(|v| v.as_str()) {
-            cmd.push("--default".to_string());
-            cmd.push(default.to_string());
+    #[test]
+    fn reads_a_babashka_session() {
+        let messages: Vec<Message> = read_all(BABASHKA_SESSION)
+            .into_iter()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[0].str("op"), Some("describe"));
+        assert_eq!(messages[1].str("op"), Some("invoke"));
+        assert_eq!(
+            messages[1].str("var"),
+            Some("pod.enigmacurry.script-wizard/ask*")
+        );
+        assert_eq!(
+            messages[1].str("args"),
+            Some(r#"["What is your name?",{"default":"World"}]"#)
+        );
+        assert_eq!(
+            messages[1].str("id"),
+            Some("1c9d8a42-5e3b-4c1f-a7d2-0e8b6f4a3c91")
+        );
+        assert_eq!(messages[2].str("op"), Some("shutdown"));
+    }
+
+    /// A client that has gone away.
+    struct Closed;
+
+    impl Write for Closed {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::ErrorKind::BrokenPipe.into())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
         }
     }
-    cmd.extend(["--cancel-code".to_string(), "2".to_string()]);
-    Ok(cmd)
-}
 
-fn build_select_args(args: &[JsonValue]) -> Result<Vec<String>, String> {
-    let question = args
-        .first()
-        .and_then(|v| v.as_str())
-        .ok_or("select requires a question string")?;
-    let options = args
-        .get(1)
-        .and_then(|v| v.as_array())
-        .ok_or("select requires an options array")?;
-
-    let mut cmd = vec!["select".to_string(), question.to_string()];
-    for opt in options {
-        if let Some(s) = opt.as_str() {
-            cmd.push(s.to_string());
+    #[test]
+    fn stops_when_responses_cant_be_written() {
+        let mut input = BABASHKA_SESSION;
+        let error = serve(&mut input, &mut Closed, Format::Json).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::BrokenPipe);
+        // Only the describe request was read:
+        assert_eq!(read_all(input).len(), 2);
+    }
+
+    #[test]
+    fn keeps_values_that_are_not_strings() {
+        let messages = read_all(b"d5:counti42e5:flagsl1:ai-1ee4:metad4:deepli1eee2:op6:invokee");
+        let msg = messages[0].as_ref().unwrap();
+        assert_eq!(msg.get("count"), Some(&BencodeObject::Integer(42)));
+        assert_eq!(
+            msg.get("flags"),
+            Some(&BencodeObject::List(vec![
+                BencodeObject::Bytes(Cow::Borrowed(b"a")),
+                BencodeObject::Integer(-1),
+            ]))
+        );
+        match msg.get("meta") {
+            Some(BencodeObject::Dict(meta)) => assert_eq!(
+                meta.get(&b"deep"[..]),
+                Some(&BencodeObject::List(vec![BencodeObject::Integer(1)]))
+            ),
+            other => panic!("expected a dict, got {:?}", other),
         }
+        assert_eq!(msg.str("op"), Some("invoke"));
+    }
+
+    #[test]
+    fn reports_malformed_messages_and_carries_on() {
+        let messages = read_all(b"li1eed2:op8:describee");
+        assert!(matches!(messages[0], Err(ReadError::Malformed { .. })));
+        assert_eq!(messages[1].as_ref().unwrap().str("op"), Some("describe"));
+
+        let messages = read_all(b"d2:opi1x2ee");
+        assert!(matches!(messages[0], Err(ReadError::Malformed { .. })));
+
+        let messages = read_all(b"d2:op8:descr");
+        assert!(matches!(messages[..], [Err(ReadError::Truncated)]));
+    }
+
+    #[test]
+    fn reads_back_its_own_responses() {
+        let mut out = vec![];
+        let data = error_data("canceled", json!({"prompt": "ask"}));
+        write_invoke_error(&mut out, Format::Edn, Some("7"), "canceled", &data).unwrap();
+        write_describe_response(&mut out, Format::Edn).unwrap();
+        let messages = read_all(&out);
+        let error = messages[0].as_ref().unwrap();
+        assert_eq!(error.str("ex-message"), Some("canceled"));
+        assert_eq!(error.str("id"), Some("7"));
+        assert_eq!(
+            error.str("ex-data"),
+            Some("{:type :script-wizard/canceled, :prompt \"ask\"}")
+        );
+        let describe = messages[1].as_ref().unwrap();
+        assert_eq!(describe.str("format"), Some("edn"));
+        assert!(matches!(
+            describe.get("namespaces"),
+            Some(BencodeObject::List(_))
+        ));
     }
 
-    if let Some(opts) = args.get(2).and_then(|v| v.as_object()) {
-        if let Some(default) = opts.get("default").and_then(|v| v.as_str()) {
-            cmd.push("--default".to_string());
-            cmd.push(default.to_string());
+    /// The names of the vars in a describe response.
+    fn var_names(describe: &Message) -> Vec<String> {
+        let Some(BencodeObject::List(namespaces)) = describe.get("namespaces") else {
+            panic!("no namespaces");
+        };
+        let BencodeObject::Dict(ns) = &namespaces[0] else {
+            panic!("namespace is not a dict");
+        };
+        let Some(BencodeObject::List(vars)) = ns.get(&b"vars"[..]) else {
+            panic!("no vars");
+        };
+        vars.iter()
+            .filter_map(|var| match var {
+                BencodeObject::Dict(var) => match var.get(&b"name"[..]) {
+                    Some(BencodeObject::Bytes(name)) => Some(String::from_utf8_lossy(name).into()),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// A value the command line would accept for `arg`.
+    fn sample(arg: &Arg) -> JsonValue {
+        if let Some(default) = arg.get_default_values().first() {
+            return json!(default.to_string_lossy());
+        }
+        if let Some(value) = arg.get_possible_values().first() {
+            return json!(value.get_name());
+        }
+        match (arg.get_action(), arg.get_value_names()) {
+            (ArgAction::SetTrue, _) => json!(true),
+            (ArgAction::Append, _) => json!(["a"]),
+            (_, Some([name])) if name == "JSON_ARRAY" => json!([]),
+            _ => json!("1"),
         }
     }
-    cmd.extend(["--cancel-code".to_string(), "2".to_string()]);
-    Ok(cmd)
-}
 
-fn build_date_args(args: &[JsonValue]) -> Result<Vec<String>, String> {
-    let question = args
-        .first()
-        .and_then(|v| v.as_str())
-        .ok_or("date requires a question string")?;
-    let mut cmd = vec!["date".to_string(), question.to_string()];
-
-    if let Some(opts) = args.get(1).and_then(|v| v.as_object()) {
-        for (key, flag) in [
-            ("default", "--default"),
-            ("format", "--format"),
-            ("min-date", "--min-date"),
-            ("max-date", "--max-date"),
-            ("starting-date", "--starting-date"),
-            ("week-start", "--week-start"),
-            ("help-message", "--help-message"),
-        ] {
-            if let Some(val) = opts.get(key).and_then(|v| v.as_str()) {
-                cmd.push(flag.to_string());
-                cmd.push(val.to_string());
+    #[test]
+    fn cli_and_pod_stay_in_sync() {
+        let mut out = vec![];
+        write_describe_response(&mut out, Format::Json).unwrap();
+        let names = var_names(read_all(&out)[0].as_ref().unwrap());
+
+        for subcommand in &kinds() {
+            let name = subcommand.get_name();
+            assert!(names.contains(&name.to_string()), "no {} var", name);
+            assert!(names.contains(&format!("{}*", name)), "no {}* var", name);
+
+            // The global options are there too, and documented:
+            let meta = var_meta(subcommand);
+            for global in [
+                "key",
+                "timeout",
+                "interrupt-code",
+                "error-code",
+                "answers",
+                "record",
+            ] {
+                assert!(
+                    meta.contains(&format!("\\n    :{} - ", global)),
+                    "{} :{}",
+                    name,
+                    global
+                );
+            }
+            assert!(!meta.contains("\\n    :help "), "{} :help", name);
+
+            // Each argument on its own (some of them conflict), with
+            // empty lists for the list parameters:
+            for arg in arguments(subcommand) {
+                let mut args: Vec<JsonValue> = arguments(subcommand)
+                    .filter(|a| is_param(subcommand, a))
+                    .map(|p| match p.get_action() {
+                        ArgAction::Append if p != arg => json!([]),
+                        _ => sample(p),
+                    })
+                    .collect();
+                if !is_param(subcommand, arg) {
+                    args.push(json!({ keyword(arg): sample(arg) }));
+                }
+                let argv = command_line(subcommand, &args)
+                    .unwrap_or_else(|e| panic!("{} :{}: {}", name, keyword(arg), e.describe().0));
+                let matches = Cli::command()
+                    .try_get_matches_from(&argv)
+                    .unwrap_or_else(|e| panic!("{:?}: {}", argv, e));
+                let (_, matches) = matches.subcommand().unwrap();
+                assert_eq!(
+                    matches.value_source(arg.get_id().as_str()),
+                    Some(clap::parser::ValueSource::CommandLine),
+                    "{:?} does not set {}",
+                    argv,
+                    arg.get_id()
+                );
             }
         }
+        assert!(!names.contains(&"pod".to_string()));
+        assert!(!names.contains(&"help".to_string()));
     }
-    cmd.extend(["--cancel-code".to_string(), "2".to_string()]);
-    Ok(cmd)
-}
 
-fn build_editor_args(args: &[JsonValue]) -> Result<Vec<String>, String> {
-    let message = args
-        .first()
-        .and_then(|v| v.as_str())
-        .ok_or("editor requires a message string")?;
-    let mut cmd = vec!["editor".to_string(), message.to_string()];
-
-    if let Some(opts) = args.get(1).and_then(|v| v.as_object()) {
-        for (key, flag) in [
-            ("default", "--default"),
-            ("help-message", "--help-message"),
-            ("file-extension", "--file-extension"),
-        ] {
-            if let Some(val) = opts.get(key).and_then(|v| v.as_str()) {
-                cmd.push(flag.to_string());
-                cmd.push(val.to_string());
+    #[test]
+    fn invokes_prompts_in_process() {
+        let script = Rc::new(ScriptedBackend::new([
+            json!("Dear Ada,\n\n  Thanks!\n"),
+            json!(["a", "c"]),
+            json!("8080"),
+            json!(null),
+        ]));
+        with_backend(script, || {
+            // Results keep their type, and text keeps every line:
+            let letter = handle_invoke("editor*", "[\"Letter\"]", Format::Edn);
+            assert_eq!(letter.ok(), Some(json!("Dear Ada,\n\n  Thanks!\n")));
+            let picked = handle_invoke("select*", "[\"Pick\" [\"a\" \"b\" \"c\"]]", Format::Edn);
+            assert_eq!(picked.ok(), Some(json!(["a", "c"])));
+            let port = handle_invoke("number*", "[\"Port?\" {:integer true}]", Format::Edn);
+            assert_eq!(port.ok(), Some(json!(8080)));
+            match handle_invoke("editor*", "[\"Letter\" {:cancel-code 4}]", Format::Edn) {
+                Err(e) => {
+                    let (message, data) = e.describe();
+                    assert_eq!(message, "canceled");
+                    assert_eq!(
+                        Format::Edn.encode(&data),
+                        concat!(
+                            "{:type :script-wizard/canceled, :prompt \"editor\", ",
+                            ":error \"canceled\", :code 4}"
+                        )
+                    );
+                }
+                Ok(v) => panic!("answered {}", v),
             }
-        }
+        });
+
+        // A whole session, with the answer sent back as it was given:
+        let mut out = vec![];
+        let script = Rc::new(ScriptedBackend::new([json!("Ada\nLovelace")]));
+        let mut session = BABASHKA_SESSION;
+        with_backend(script, || serve(&mut session, &mut out, Format::Json)).unwrap();
+        let responses: Vec<Message> = read_all(&out).into_iter().map(Result::unwrap).collect();
+        assert_eq!(responses.len(), 2);
+        assert_eq!(
+            responses[1].str("id"),
+            Some("1c9d8a42-5e3b-4c1f-a7d2-0e8b6f4a3c91")
+        );
+        assert_eq!(responses[1].str("value"), Some(r#""Ada\nLovelace""#));
     }
-    cmd.extend(["--cancel-code".to_string(), "2".to_string()]);
-    Ok(cmd)
-}
 
-pub fn run_pod() {
-    let stdin = io::stdin();
-    let stdout = io::stdout();
-    let mut reader = stdin.lock();
-    let mut writer = stdout.lock();
+    #[test]
+    fn wraps_vars_to_handle_cancel() {
+        let kind = |name: &str| kinds().into_iter().find(|k| k.get_name() == name).unwrap();
+        assert_eq!(
+            var_code(&kind("confirm")),
+            concat!(
+                "(defn confirm [question & {:as opts}] ",
+                "(with-cancel opts #(pod.enigmacurry.script-wizard/confirm* question %)))"
+            )
+        );
+        let meta = var_meta(&kind("confirm"));
+        assert!(meta.contains("\\n    :on-cancel - Return this when the prompt is canceled"));
+        assert!(meta.contains(" error-code on-cancel]}])}"));
 
-    while let Some(msg) = read_bencode_message(&mut reader) {
-        let op = msg.get("op").map(|s| s.as_str()).unwrap_or("");
+        // The helper returns :on-cancel, if given, in place of throwing:
+        assert!(WITH_CANCEL.contains("(contains? opts :on-cancel) (:on-cancel opts)"));
+        let mut out = vec![];
+        write_describe_response(&mut out, Format::Edn).unwrap();
+        let describe = String::from_utf8(out).unwrap();
+        assert!(describe.contains(&format!("4:code{}:{}", WITH_CANCEL.len(), WITH_CANCEL)));
 
-        match op {
-            "describe" => {
-                write_describe_response(&mut writer);
+        // A menu returns nil when canceled, unless told otherwise:
+        let menu = var_code(&kind("menu"));
+        assert!(menu.contains("(let [opts (merge {:on-cancel nil} opts)] "));
+        assert!(menu.contains("(if (= ::canceled choice) (:on-cancel opts) "));
+    }
+
+    #[test]
+    fn describes_cancel_in_every_format() {
+        let canceled = InvokeError::Prompt {
+            prompt: "confirm".to_string(),
+            error: WizardError::Canceled,
+            code: 1,
+        };
+        let (message, data) = canceled.describe();
+        assert_eq!(message, "canceled");
+        assert_eq!(
+            Format::Edn.encode(&data),
+            r#"{:type :script-wizard/canceled, :prompt "confirm", :error "canceled", :code 1}"#
+        );
+        assert_eq!(
+            Format::TransitJson.encode(&data),
+            concat!(
+                r#"["^ ","~:type","~:script-wizard/canceled","~:prompt","confirm","#,
+                r#""~:error","canceled","~:code",1]"#
+            )
+        );
+        // JSON has no keywords, so the wrapper makes :type one:
+        assert_eq!(
+            Format::Json.encode(&data),
+            r#"{"type":"script-wizard/canceled","prompt":"confirm","error":"canceled","code":1}"#
+        );
+    }
+
+    #[test]
+    fn applies_global_options_to_one_call() {
+        let dir = std::env::temp_dir().join(format!("script-wizard-pod-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let answers = dir.join("answers.json");
+        std::fs::write(&answers, r#"{"name": "Ada"}"#).unwrap();
+
+        let script = Rc::new(ScriptedBackend::new([json!("Bob")]));
+        with_backend(script, || {
+            let args = json!(["Name?", {"key": "name", "answers": answers}]).to_string();
+            assert_eq!(
+                handle_invoke("ask*", &args, Format::Json).ok(),
+                Some(json!("Ada"))
+            );
+            let args = json!(["Name?", {"key": "name"}]).to_string();
+            assert_eq!(
+                handle_invoke("ask*", &args, Format::Json).ok(),
+                Some(json!("Bob"))
+            );
+            // Out of scripted answers, the prompt fails:
+            let args = json!(["Name?", {"error-code": 3}]).to_string();
+            match handle_invoke("ask*", &args, Format::Json) {
+                Err(InvokeError::Prompt { code, .. }) => assert_eq!(code, 3),
+                _ => panic!("expected the prompt to fail"),
             }
-            "invoke" => {
-                let id = msg.get("id").map(|s| s.as_str()).unwrap_or("");
-                let var = msg.get("var").map(|s| s.as_str()).unwrap_or("");
-                let args = msg.get("args").map(|s| s.as_str()).unwrap_or("[]");
+        });
+        std::fs::remove_dir_all(dir).unwrap();
+    }
 
-                // eprintln!("[pod] invoke var={} args={}", var, args);
-                match handle_invoke(var, args) {
-                    Ok(value) => {
-                        // eprintln!("[pod] ok: {}", value);
-                        write_invoke_response(&mut writer, id, &value);
-                    }
-                    Err(e) => {
-                        // eprintln!("[pod] err: {}", e);
-                        write_invoke_error(&mut writer, id, &e);
-                    }
+    #[test]
+    fn invokes_subcommands_with_keyword_options() {
+        let script = Rc::new(ScriptedBackend::new([json!("no"), json!("b"), json!(null)]));
+        with_backend(script, || {
+            let confirmed = handle_invoke(
+                "pod.enigmacurry.script-wizard/confirm*",
+                "[\"Continue?\" {:default :yes}]",
+                Format::Edn,
+            );
+            assert_eq!(confirmed.ok(), Some(json!(false)));
+            let chosen = handle_invoke(
+                "choose*",
+                "[\"Pick\" [\"a\" \"b\"] {:json true}]",
+                Format::Edn,
+            );
+            assert_eq!(chosen.ok(), Some(json!("\"b\"")));
+            match handle_invoke("ask*", "[\"Name?\" {:cancel-code 5}]", Format::Edn) {
+                Err(e) => {
+                    let (message, data) = e.describe();
+                    assert_eq!(message, "canceled");
+                    assert_eq!(
+                        Format::Edn.encode(&data),
+                        concat!(
+                            "{:type :script-wizard/canceled, :prompt \"ask\", ",
+                            ":error \"canceled\", :code 5}"
+                        )
+                    );
                 }
+                Ok(v) => panic!("answered {}", v),
             }
-            "shutdown" => {
-                break;
-            }
-            _ => {}
+        });
+        assert!(matches!(
+            handle_invoke("ask*", "[\"Name?\" {:colour \"red\"}]", Format::Edn),
+            Err(InvokeError::BadArgs(_))
+        ));
+        assert!(matches!(
+            handle_invoke("ask*", "[]", Format::Edn),
+            Err(InvokeError::BadArgs(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_malformed_transit() {
+        for args in [
+            r#"["Name?",["^ ","^!","x"]]"#,
+            r#"["~#set"#,
+            r#"["^ ","^Z9","x"]"#,
+        ] {
+            let invoked = handle_invoke("ask*", args, Format::TransitJson);
+            assert!(matches!(invoked, Err(InvokeError::BadArgs(_))), "{}", args);
         }
     }
 }
@@ -0,0 +1,232 @@
+//! What our own prompts share: the picker, the path input and the
+//! stepper each handle keys and draw themselves, while this reads the
+//! keys in raw mode, clears what was drawn, and draws in the styles of
+//! inquire's render config, so they look like inquire's own prompts.
+
+use crate::ask::TimedOut;
+use crossterm::event::{self, Event, KeyEvent, KeyEventKind};
+use crossterm::style::{Attribute, Color, ContentStyle, Print, PrintStyledContent};
+use crossterm::{cursor, queue, terminal};
+use inquire::error::{InquireError, InquireResult};
+use inquire::ui::{Attributes, RenderConfig, StyleSheet, Styled};
+use std::io::{self, Write};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+/// What a key did.
+#[derive(Debug, PartialEq)]
+pub(crate) enum Action<T> {
+    /// Changed what's shown, or nothing
+    Redraw,
+    /// Answered with this
+    Done(T),
+    Cancel,
+    Interrupt,
+}
+
+/// A prompt that handles one key at a time, and draws itself.
+pub(crate) trait KeyPrompt {
+    type Answer;
+
+    fn question(&self) -> &str;
+
+    /// Do what `key` says.
+    fn handle(&mut self, key: KeyEvent) -> InquireResult<Action<Self::Answer>>;
+
+    /// Draw the prompt from the start of the line the cursor is on, and
+    /// return how many lines below that the cursor was left.
+    fn draw(&mut self, out: &mut impl Write) -> io::Result<usize>;
+
+    /// How the answer is shown once it's given.
+    fn show(&self, answer: &Self::Answer) -> String;
+
+    /// When to give up, unless a key is pressed before.
+    fn deadline(&self) -> Option<Instant> {
+        None
+    }
+}
+
+/// Ask on the terminal, and return the answer.
+pub(crate) fn prompt<P: KeyPrompt>(mut prompt: P) -> InquireResult<P::Answer> {
+    let mut stderr = io::stderr();
+    terminal::enable_raw_mode()?;
+    let result = run(&mut prompt, &mut stderr);
+    let _ = queue!(stderr, cursor::Show);
+    let _ = stderr.flush();
+    terminal::disable_raw_mode()?;
+    result
+}
+
+fn run<P: KeyPrompt>(prompt: &mut P, out: &mut impl Write) -> InquireResult<P::Answer> {
+    // How many lines below the start of the prompt the cursor is:
+    let mut below = 0;
+    loop {
+        clear(out, below)?;
+        below = prompt.draw(out)?;
+        out.flush()?;
+        if let Some(deadline) = prompt.deadline() {
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                clear(out, below)?;
+                out.flush()?;
+                return Err(InquireError::Custom(Box::new(TimedOut)));
+            }
+            if !event::poll(left.min(Duration::from_secs(1)))? {
+                continue;
+            }
+        }
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind == KeyEventKind::Release {
+            continue;
+        }
+        let (shown, result) = match prompt.handle(key)? {
+            Action::Redraw => continue,
+            Action::Done(answer) => (Some(prompt.show(&answer)), Ok(answer)),
+            Action::Cancel => (None, Err(InquireError::OperationCanceled)),
+            Action::Interrupt => (None, Err(InquireError::OperationInterrupted)),
+        };
+        clear(out, below)?;
+        finish(out, prompt.question(), shown.as_deref())?;
+        return result;
+    }
+}
+
+/// Clear what was drawn, with the cursor `below` lines below the start
+/// of the prompt, and leave it there.
+fn clear(out: &mut impl Write, below: usize) -> io::Result<()> {
+    queue!(out, cursor::MoveToColumn(0))?;
+    if below > 0 {
+        queue!(out, cursor::MoveUp(below as u16))?;
+    }
+    queue!(out, terminal::Clear(terminal::ClearType::FromCursorDown))
+}
+
+/// Replace the prompt with the answer, or with a note that it was
+/// canceled.
+fn finish(out: &mut impl Write, question: &str, answer: Option<&str>) -> io::Result<()> {
+    let config = render_config();
+    let question = format!(" {} ", question);
+    match answer {
+        Some(answer) => print(
+            out,
+            &[
+                styled(&config.answered_prompt_prefix),
+                (&question, config.prompt),
+                (answer, config.answer),
+            ],
+            usize::MAX,
+        )?,
+        None => print(
+            out,
+            &[
+                styled(&config.prompt_prefix),
+                (&question, config.prompt),
+                styled(&config.canceled_prompt_indicator),
+            ],
+            usize::MAX,
+        )?,
+    }
+    queue!(out, Print("\r\n"))?;
+    out.flush()
+}
+
+/// The styles inquire draws its own prompts in.
+pub(crate) fn render_config() -> &'static RenderConfig<'static> {
+    static CONFIG: OnceLock<RenderConfig<'static>> = OnceLock::new();
+    CONFIG.get_or_init(RenderConfig::default)
+}
+
+/// A piece of text from the render config, with its style.
+pub(crate) fn styled<'a>(styled: &Styled<&'a str>) -> (&'a str, StyleSheet) {
+    (styled.content, styled.style)
+}
+
+/// How many characters fit on a line.
+pub(crate) fn width() -> io::Result<usize> {
+    // A terminal that doesn't know its size says 0:
+    Ok(match terminal::size()?.0 {
+        0 => usize::MAX,
+        width => width as usize,
+    })
+}
+
+/// Print each part in its style, leaving out what goes past `room`
+/// characters.
+pub(crate) fn print(
+    out: &mut impl Write,
+    parts: &[(&str, StyleSheet)],
+    mut room: usize,
+) -> io::Result<()> {
+    for (text, sheet) in parts {
+        let text: String = text.chars().take(room).collect();
+        room -= text.chars().count();
+        queue!(out, PrintStyledContent(content_style(*sheet).apply(text)))?;
+    }
+    Ok(())
+}
+
+/// Print the question, and what's typed so far, or else `placeholder`.
+/// Return the column just after what's typed.
+pub(crate) fn print_question(
+    out: &mut impl Write,
+    question: &str,
+    input: &str,
+    placeholder: Option<&str>,
+    room: usize,
+) -> io::Result<usize> {
+    let config = render_config();
+    let question = format!(" {} ", question);
+    let prefix = styled(&config.prompt_prefix);
+    print(
+        out,
+        &[
+            prefix,
+            (&question, config.prompt),
+            (input, config.text_input),
+        ],
+        room,
+    )?;
+    if let (true, Some(placeholder)) = (input.is_empty(), placeholder) {
+        let placeholder = format!("({})", placeholder);
+        print(out, &[(&placeholder, config.placeholder)], usize::MAX)?;
+    }
+    Ok([prefix.0, &question, input]
+        .iter()
+        .map(|s| s.chars().count())
+        .sum())
+}
+
+/// Print why the last answer wasn't accepted, on a line of its own.
+pub(crate) fn print_error(out: &mut impl Write, error: &str) -> io::Result<()> {
+    let config = render_config().error_message;
+    let message = format!(" {}", error);
+    queue!(out, Print("\r\n"))?;
+    print(
+        out,
+        &[styled(&config.prefix), (&message, config.message)],
+        usize::MAX,
+    )
+}
+
+/// Print the help message, on a line of its own.
+pub(crate) fn print_help(out: &mut impl Write, help: &str, room: usize) -> io::Result<()> {
+    let help = format!("[{}]", help);
+    queue!(out, Print("\r\n"))?;
+    print(out, &[(&help, render_config().help_message)], room)
+}
+
+/// inquire's style sheet, as crossterm's.
+fn content_style(sheet: StyleSheet) -> ContentStyle {
+    let mut style = ContentStyle::new();
+    style.foreground_color = sheet.fg.map(Color::from);
+    style.background_color = sheet.bg.map(Color::from);
+    if sheet.att.contains(Attributes::BOLD) {
+        style.attributes.set(Attribute::Bold);
+    }
+    if sheet.att.contains(Attributes::ITALIC) {
+        style.attributes.set(Attribute::Italic);
+    }
+    style
+}
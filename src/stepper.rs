@@ -0,0 +1,196 @@
+//! The prompt behind `number --step`: a line of input like inquire's,
+//! but the up and down keys add or subtract the step.
+
+use crate::backend::Step;
+use crate::rawprompt::{self, Action, KeyPrompt};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use crossterm::{cursor, queue};
+use inquire::error::{InquireError, InquireResult};
+use inquire::validator::{ErrorMessage, StringValidator, Validation};
+use std::io::{self, Write};
+
+pub(crate) struct Stepper<'a> {
+    question: &'a str,
+    default: Option<&'a str>,
+    help: Option<&'a str>,
+    validator: Option<Box<dyn StringValidator>>,
+    step: Step,
+    input: String,
+    /// Why the last answer wasn't accepted
+    error: Option<String>,
+}
+
+impl<'a> Stepper<'a> {
+    pub(crate) fn new(question: &'a str, step: Step) -> Self {
+        Stepper {
+            question,
+            default: None,
+            help: None,
+            validator: None,
+            step,
+            input: String::new(),
+            error: None,
+        }
+    }
+
+    pub(crate) fn default(mut self, default: Option<&'a str>) -> Self {
+        self.default = default;
+        self
+    }
+
+    pub(crate) fn help(mut self, help: Option<&'a str>) -> Self {
+        self.help = help;
+        self
+    }
+
+    pub(crate) fn validator(mut self, validator: Option<Box<dyn StringValidator>>) -> Self {
+        self.validator = validator;
+        self
+    }
+
+    /// Start with `input` already typed.
+    pub(crate) fn input(mut self, input: Option<String>) -> Self {
+        self.input = input.unwrap_or_default();
+        self
+    }
+
+    /// Ask on the terminal, and return the answer.
+    pub(crate) fn prompt(self) -> InquireResult<String> {
+        rawprompt::prompt(self)
+    }
+}
+
+impl KeyPrompt for Stepper<'_> {
+    type Answer = String;
+
+    fn question(&self) -> &str {
+        self.question
+    }
+
+    fn handle(&mut self, key: KeyEvent) -> InquireResult<Action<String>> {
+        if key.modifiers.contains(KeyModifiers::CONTROL) {
+            return Ok(match key.code {
+                KeyCode::Char('c') => Action::Interrupt,
+                _ => Action::Redraw,
+            });
+        }
+        match key.code {
+            KeyCode::Esc => return Ok(Action::Cancel),
+            KeyCode::Enter => {
+                let answer = match (self.input.as_str(), self.default) {
+                    ("", Some(default)) => default.to_string(),
+                    (input, _) => input.to_string(),
+                };
+                let validation = match &self.validator {
+                    Some(validator) => validator.validate(&answer).map_err(InquireError::Custom)?,
+                    None => Validation::Valid,
+                };
+                match validation {
+                    Validation::Valid => return Ok(Action::Done(answer)),
+                    Validation::Invalid(ErrorMessage::Custom(reason)) => self.error = Some(reason),
+                    Validation::Invalid(ErrorMessage::Default) => {
+                        self.error = Some("Invalid input".to_string())
+                    }
+                }
+            }
+            KeyCode::Up | KeyCode::Down => {
+                // Blank input steps from the default, or from zero:
+                let current = match (self.input.trim(), self.default) {
+                    ("", Some(default)) => default,
+                    ("", None) => "0",
+                    (input, _) => input,
+                };
+                if let Some(stepped) = self.step.apply(current, key.code == KeyCode::Up) {
+                    self.input = stepped;
+                    self.error = None;
+                }
+            }
+            KeyCode::Backspace => {
+                self.input.pop();
+            }
+            KeyCode::Char(c) => self.input.push(c),
+            _ => {}
+        }
+        Ok(Action::Redraw)
+    }
+
+    fn draw(&mut self, out: &mut impl Write) -> io::Result<usize> {
+        let (input, default) = (&self.input, self.default);
+        let column = rawprompt::print_question(out, self.question, input, default, usize::MAX)?;
+        let mut lines = 1;
+        if let Some(error) = &self.error {
+            rawprompt::print_error(out, error)?;
+            lines += 1;
+        }
+        if let Some(help) = self.help {
+            rawprompt::print_help(out, help, usize::MAX)?;
+            lines += 1;
+        }
+        // Put the cursor back at the end of the input:
+        if lines > 1 {
+            queue!(out, cursor::MoveUp(lines as u16 - 1))?;
+        }
+        queue!(out, cursor::MoveToColumn(column as u16))?;
+        Ok(0)
+    }
+
+    fn show(&self, answer: &String) -> String {
+        answer.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(stepper: &mut Stepper, code: KeyCode) -> Action<String> {
+        stepper
+            .handle(KeyEvent::new(code, KeyModifiers::NONE))
+            .unwrap()
+    }
+
+    #[test]
+    fn steps_with_up_and_down() {
+        let step = Step {
+            by: 5.0,
+            integer: true,
+            min: Some(0.0),
+            max: Some(12.0),
+        };
+        let mut stepper = Stepper::new("How many?", step).default(Some("3"));
+        press(&mut stepper, KeyCode::Up);
+        assert_eq!(stepper.input, "8");
+        press(&mut stepper, KeyCode::Up);
+        assert_eq!(stepper.input, "12");
+        for _ in 0..4 {
+            press(&mut stepper, KeyCode::Down);
+        }
+        assert_eq!(stepper.input, "0");
+        press(&mut stepper, KeyCode::Char('7'));
+        assert_eq!(
+            press(&mut stepper, KeyCode::Enter),
+            Action::Done("07".to_string())
+        );
+
+        let validator = |input: &str| match input.parse::<f64>() {
+            Ok(_) => Ok(Validation::Valid),
+            Err(_) => Ok(Validation::Invalid("not a number".into())),
+        };
+        let mut stepper = Stepper::new(
+            "How much?",
+            Step {
+                by: 0.1,
+                integer: false,
+                ..step
+            },
+        )
+        .validator(Some(Box::new(validator)))
+        .input(Some("0.2".to_string()));
+        press(&mut stepper, KeyCode::Up);
+        assert_eq!(stepper.input, "0.3");
+        press(&mut stepper, KeyCode::Char('x'));
+        assert_eq!(press(&mut stepper, KeyCode::Enter), Action::Redraw);
+        assert_eq!(stepper.error.as_deref(), Some("not a number"));
+        assert_eq!(press(&mut stepper, KeyCode::Esc), Action::Cancel);
+    }
+}